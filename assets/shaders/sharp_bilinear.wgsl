@@ -0,0 +1,24 @@
+// nearest neighbour inside each game pixel with a linear blend at most one window pixel wide
+// along its edges, so non integer scales stay sharp without uneven pixel sizes
+
+@group(1) @binding(0)
+var<uniform> pixels_per_texel: f32;
+@group(1) @binding(1)
+var screen_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var screen_sampler: sampler;
+
+struct FragmentInput {
+    #import bevy_sprite::mesh2d_vertex_output
+};
+
+@fragment
+fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(screen_texture));
+    let texel = in.uv * size;
+    // how far from the texel center it stays flat
+    let region = vec2<f32>(0.5 - 0.5 / pixels_per_texel);
+    let from_center = fract(texel) - 0.5;
+    let blend = (from_center - clamp(from_center, -region, region)) * pixels_per_texel + 0.5;
+    return textureSample(screen_texture, screen_sampler, (floor(texel) + blend) / size);
+}
@@ -0,0 +1,112 @@
+// compares checking every hurtbox against every hitbox with the spatial hash broadphase
+// on a map that's filled with wall tiles and traffic
+// the game's modules are only partly used here
+#![allow(dead_code, unused_imports)]
+
+#[path = "../src/consts.rs"]
+mod consts;
+#[path = "../src/shapes.rs"]
+mod shapes;
+#[path = "../src/spatial_hash.rs"]
+mod spatial_hash;
+
+use bevy::prelude::{Entity, Vec2};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use shapes::{overlap, Box, ConvexPolygon, ToWorldShape, WorldShape};
+use spatial_hash::SpatialHash;
+
+const TILE: f32 = consts::TILE_SIZE as f32;
+
+// every tile is a wall and every other row has a lane of cars
+fn dense_map() -> Vec<WorldShape> {
+    let wall = Box {
+        offset: Vec2::ZERO,
+        size: Vec2::splat(TILE),
+    };
+    let car = ConvexPolygon::rounded_box(Vec2::ZERO, Vec2::new(14.0, 8.0), 2.0);
+
+    let mut hitboxes = vec![];
+    for y in 0..16 {
+        for x in 0..16 {
+            let center = Vec2::new(x as f32, y as f32) * TILE + TILE / 2.0;
+            hitboxes.push(wall.to_world_shape(center));
+        }
+    }
+    for y in (0..16).step_by(2) {
+        for x in (0..16).step_by(3) {
+            let center = Vec2::new(x as f32 * TILE + 7.0, y as f32 * TILE + 4.0);
+            hitboxes.push(car.to_world_shape(center));
+        }
+    }
+    hitboxes
+}
+
+fn players() -> Vec<WorldShape> {
+    let hurtbox = Box {
+        offset: Vec2::new(-0.5, 0.0),
+        size: Vec2::new(7.0, 8.0),
+    };
+    (0..4)
+        .map(|i| hurtbox.to_world_shape(Vec2::new(20.0 + i as f32 * 30.0, 60.0)))
+        .collect()
+}
+
+fn brute_force(hurtboxes: &[WorldShape], hitboxes: &[WorldShape]) -> usize {
+    hurtboxes
+        .iter()
+        .flat_map(|hurt| hitboxes.iter().filter_map(move |hit| overlap(hurt, hit)))
+        .count()
+}
+
+fn build_hash(hitboxes: &[WorldShape]) -> SpatialHash {
+    let mut hash = SpatialHash::default();
+    for (i, hit) in hitboxes.iter().enumerate() {
+        let (min, max) = hit.bounds();
+        hash.update(Entity::from_raw(i as u32), min, max);
+    }
+    hash
+}
+
+fn broadphase(hash: &SpatialHash, hurtboxes: &[WorldShape], hitboxes: &[WorldShape]) -> usize {
+    hurtboxes
+        .iter()
+        .map(|hurt| {
+            let (min, max) = hurt.bounds();
+            hash.query(min, max)
+                .into_iter()
+                .filter_map(|e| overlap(hurt, &hitboxes[e.index() as usize]))
+                .count()
+        })
+        .sum()
+}
+
+fn collisions(c: &mut Criterion) {
+    let hitboxes = dense_map();
+    let hurtboxes = players();
+    let hash = build_hash(&hitboxes);
+    assert_eq!(
+        brute_force(&hurtboxes, &hitboxes),
+        broadphase(&hash, &hurtboxes, &hitboxes)
+    );
+
+    let mut group = c.benchmark_group("dense map");
+    group.bench_function("all pairs", |b| {
+        b.iter(|| brute_force(black_box(&hurtboxes), black_box(&hitboxes)))
+    });
+    group.bench_function("spatial hash", |b| {
+        b.iter(|| {
+            broadphase(
+                black_box(&hash),
+                black_box(&hurtboxes),
+                black_box(&hitboxes),
+            )
+        })
+    });
+    group.bench_function("spatial hash rebuild", |b| {
+        b.iter(|| build_hash(black_box(&hitboxes)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, collisions);
+criterion_main!(benches);
@@ -0,0 +1,190 @@
+// frame time under heavy emission, recycling the particle pool against spawning and
+// despawning a sprite per particle, both settle at ~3000 live particles that move
+// through the game's own movement systems
+// the game's modules are only partly used here
+#![allow(dead_code, unused_imports)]
+
+#[path = "../src/animation.rs"]
+mod animation;
+#[path = "../src/consts.rs"]
+mod consts;
+#[path = "../src/coordinates.rs"]
+mod coordinates;
+#[path = "../src/particles.rs"]
+mod particles;
+
+use animation::AnimationFrameEvent;
+use bevy::prelude::*;
+use bevy::time::{TimePlugin, TimeUpdateStrategy};
+use consts::AppState;
+use coordinates::{Acceleration, Layer, MovementPlugin, PixelPosition, Velocity};
+use criterion::{criterion_group, criterion_main, Criterion};
+use particles::{ColorStop, EmitWhen, Emitter, EmitterConfig, ParticleSystem, SizeStop};
+use std::time::{Duration, Instant};
+
+const EMITTERS: usize = 10;
+const RATE: f32 = 600.0;
+const LIFETIME: f32 = 0.5;
+const FRAME: f32 = 1.0 / 60.0;
+// frames per sample when looking for the slowest one
+const BURST: u32 = 60;
+
+fn heavy_config() -> EmitterConfig {
+    EmitterConfig {
+        emit: EmitWhen::Always,
+        rate: RATE,
+        burst: 0,
+        lifetime: LIFETIME,
+        velocity_min: Vec2::new(-50.0, -20.0),
+        velocity_max: Vec2::new(50.0, 80.0),
+        gravity: -300.0,
+        colors: vec![
+            ColorStop {
+                at: 0.0,
+                color: [1.0, 0.0, 0.0, 1.0],
+            },
+            ColorStop {
+                at: 1.0,
+                color: [1.0, 0.0, 0.0, 0.0],
+            },
+        ],
+        sizes: vec![SizeStop { at: 0.0, size: 1.0 }],
+        layer: 3.0,
+    }
+}
+
+fn base_app() -> App {
+    let mut app = App::new();
+    app.add_plugin(TaskPoolPlugin::default())
+        .add_plugin(TypeRegistrationPlugin)
+        .add_plugin(TimePlugin)
+        .add_plugin(AssetPlugin::default())
+        .add_plugin(MovementPlugin)
+        // step time by a fixed frame so both setups emit the same amount,
+        // and the fixed update runs once per frame
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            FRAME,
+        )))
+        .insert_resource(FixedTime::new_from_secs(FRAME))
+        .add_state::<AppState>()
+        .add_event::<AnimationFrameEvent>();
+    app.world
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::InGame);
+    app
+}
+
+fn step(app: &mut App) {
+    app.update();
+}
+
+// the slowest frame of each burst, a spike there is a dropped frame even if the mean is fine
+fn worst_of_burst(app: &mut App, iters: u64) -> Duration {
+    (0..iters)
+        .map(|_| {
+            (0..BURST)
+                .map(|_| {
+                    let start = Instant::now();
+                    step(app);
+                    start.elapsed()
+                })
+                .max()
+                .unwrap_or_default()
+        })
+        .sum()
+}
+
+fn pooled_app() -> App {
+    let mut app = base_app();
+    app.add_plugin(ParticleSystem { pool_size: 4096 });
+    let config = app
+        .world
+        .resource_mut::<Assets<EmitterConfig>>()
+        .add(heavy_config());
+    for i in 0..EMITTERS {
+        app.world.spawn((
+            PixelPosition(Vec2::new(i as f32 * 12.0, 64.0)),
+            Emitter::new(config.clone()),
+        ));
+    }
+    app
+}
+
+#[derive(Resource)]
+struct ChurnConfig(EmitterConfig);
+
+#[derive(Component)]
+struct Churned(f32);
+
+// what particles.rs did before the pool, with the same per particle work
+fn churn(
+    mut commands: Commands,
+    mut q: Query<(Entity, &mut Churned, &mut Sprite)>,
+    config: Res<ChurnConfig>,
+    time: Res<Time>,
+) {
+    let config = &config.0;
+    for (entity, mut age, mut sprite) in q.iter_mut() {
+        age.0 += time.delta_seconds();
+        if age.0 > config.lifetime {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        let t = age.0 / config.lifetime;
+        sprite.color = config.color_at(t);
+        sprite.custom_size = Some(Vec2::splat(config.size_at(t)));
+    }
+    let count = (EMITTERS as f32 * config.rate * time.delta_seconds()) as usize;
+    for _ in 0..count {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: config.color_at(0.0),
+                    custom_size: Some(Vec2::new(1.0, 1.0)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            PixelPosition(Vec2::new(0.0, 64.0)),
+            Layer(config.layer),
+            Velocity(Vec2::ZERO),
+            Acceleration(Vec2::new(0.0, config.gravity)),
+            Churned(0.0),
+        ));
+    }
+}
+
+fn churn_app() -> App {
+    let mut app = base_app();
+    app.insert_resource(ChurnConfig(heavy_config()))
+        .add_system(churn);
+    app
+}
+
+fn bench_particles(c: &mut Criterion) {
+    let mut group = c.benchmark_group("heavy emission frame");
+
+    let mut pooled = pooled_app();
+    // let the live count settle before measuring
+    for _ in 0..60 {
+        step(&mut pooled);
+    }
+    group.bench_function("pooled", |b| b.iter(|| step(&mut pooled)));
+    group.bench_function("pooled, worst of burst", |b| {
+        b.iter_custom(|iters| worst_of_burst(&mut pooled, iters))
+    });
+
+    let mut churned = churn_app();
+    for _ in 0..60 {
+        step(&mut churned);
+    }
+    group.bench_function("spawn and despawn", |b| b.iter(|| step(&mut churned)));
+    group.bench_function("spawn and despawn, worst of burst", |b| {
+        b.iter_custom(|iters| worst_of_burst(&mut churned, iters))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_particles);
+criterion_main!(benches);
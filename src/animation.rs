@@ -1,64 +1,517 @@
 // borrowed from https://github.com/bevyengine/bevy/pull/1770/files
 
-use bevy::asset::Handle;
-use bevy::ecs::prelude::{Component, Query, Res};
-use bevy::prelude::{Time, Timer};
+use bevy::asset::{AssetServer, Handle};
+use bevy::ecs::prelude::{Component, Entity, EventReader, EventWriter, Mut, Query, Res};
+use bevy::math::Vec2;
+use bevy::prelude::{App, IntoSystemConfig, Plugin, Time};
+use bevy::reflect::TypeUuid;
 use bevy::sprite::{TextureAtlas, TextureAtlasSprite};
+use bevy_common_assets::ron::RonAssetPlugin;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::Duration;
 
+// things that happen on a specific frame, sent as AnimationFrameEvent when it's shown
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FrameEvent {
+    Footstep,
+    Honk,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FrameData {
+    pub index: usize,
+    pub duration: f32, // secs
+    #[serde(default)]
+    pub events: Vec<FrameEvent>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum PlaybackMode {
+    #[default]
+    Loop,
+    // stops on the last frame and sends AnimationFinished
+    Once,
+    // plays forwards then backwards without repeating the end frames
+    PingPong,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ClipData {
+    pub frames: Vec<FrameData>,
+    #[serde(default)]
+    pub mode: PlaybackMode,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub enum TransitionTrigger {
+    // sent by gameplay code with AnimationIntent
+    Intent(String),
+    // a Once clip reached its last frame
+    Finished,
+}
+
+// "from" of None matches every clip
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Transition {
+    #[serde(default)]
+    pub from: Option<String>,
+    pub on: TransitionTrigger,
+    pub to: String,
+}
+
+// named clips cut from a single sprite sheet, loaded from .anim files
+#[derive(Serialize, Deserialize, TypeUuid, Clone)]
+#[uuid = "3f4b8b0e-9a43-4d0b-8a58-1a3c2b9f6d21"]
+pub struct AnimationSet {
+    pub texture: String,
+    pub tile_size: Vec2,
+    pub columns: usize,
+    pub rows: usize,
+    pub clips: HashMap<String, ClipData>,
+    #[serde(default)]
+    pub transitions: Vec<Transition>,
+}
+impl AnimationSet {
+    pub fn texture_atlas(&self, asset_server: &AssetServer) -> TextureAtlas {
+        TextureAtlas::from_grid(
+            asset_server.load(self.texture.as_str()),
+            self.tile_size,
+            self.columns,
+            self.rows,
+            None,
+            None,
+        )
+    }
+}
+
+#[derive(Clone)]
 pub struct AnimationFrame {
     pub atlas_handle: Handle<TextureAtlas>,
     pub atlas_index: usize,
     pub duration: Duration,
+    pub events: Vec<FrameEvent>,
 }
 
+#[derive(Clone)]
 pub struct Animation {
     pub frames: Vec<AnimationFrame>,
+    pub mode: PlaybackMode,
 }
 
-#[derive(Component)]
+#[derive(Default, Clone, Component)]
 pub struct Animations {
-    pub animations: Vec<Animation>,
+    pub animations: HashMap<String, Animation>,
+    pub transitions: Vec<Transition>,
 }
+impl Animations {
+    pub fn from_set(set: &AnimationSet, atlas_handle: Handle<TextureAtlas>) -> Self {
+        Animations {
+            animations: set
+                .clips
+                .iter()
+                .map(|(name, clip)| {
+                    let frames = clip
+                        .frames
+                        .iter()
+                        .map(|frame| AnimationFrame {
+                            atlas_handle: atlas_handle.clone(),
+                            atlas_index: frame.index,
+                            duration: Duration::from_secs_f32(frame.duration),
+                            events: frame.events.clone(),
+                        })
+                        .collect();
+                    let animation = Animation {
+                        frames,
+                        mode: clip.mode,
+                    };
+                    (name.clone(), animation)
+                })
+                .collect(),
+            transitions: set.transitions.clone(),
+        }
+    }
 
-#[derive(Default, Component)]
+    // first transition out of `from` that fires on `trigger`
+    pub fn next_animation(&self, from: &str, trigger: &TransitionTrigger) -> Option<&str> {
+        self.transitions
+            .iter()
+            .find(|transition| {
+                transition.on == *trigger && transition.from.as_deref().unwrap_or(from) == from
+            })
+            .map(|transition| transition.to.as_str())
+    }
+}
+
+// ask an entity's animation state machine to react, e.g. "move" or "hit"
+pub struct AnimationIntent {
+    pub entity: Entity,
+    pub intent: &'static str,
+}
+
+pub struct AnimationFrameEvent {
+    pub entity: Entity,
+    pub event: FrameEvent,
+}
+
+// sent when the last frame of a Once clip has played out,
+// transitions handle it themselves so nothing in the game listens yet
+#[allow(dead_code)]
+pub struct AnimationFinished {
+    pub entity: Entity,
+    pub animation: String,
+}
+
+#[derive(Component)]
 pub struct Animator {
-    pub current_animation: usize,
-    pub last_animation: usize,
+    pub current_animation: String,
+    // set by play, the next update shows the first frame and sends its events,
+    // even when the clip that was already playing is played again
+    pub restarted: bool,
     pub current_frame: usize,
-    pub timer: Timer,
+    // time spent on the current frame, already scaled by speed
+    pub frame_time: Duration,
+    // playback speed multiplier, 2.0 plays twice as fast
+    pub speed: f32,
+    pub paused: bool,
+    // ping-pong clips play backwards on the way back
+    pub reversed: bool,
+    pub finished: bool,
+}
+impl Default for Animator {
+    fn default() -> Self {
+        Animator {
+            current_animation: String::new(),
+            restarted: true,
+            current_frame: 0,
+            frame_time: Duration::ZERO,
+            speed: 1.0,
+            paused: false,
+            reversed: false,
+            finished: false,
+        }
+    }
+}
+impl Animator {
+    pub fn new(animation: &str) -> Self {
+        Animator {
+            current_animation: animation.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // restart from the first frame of the named clip
+    pub fn play(&mut self, animation: &str) {
+        self.current_animation = animation.to_string();
+        self.restarted = true;
+        self.current_frame = 0;
+        self.frame_time = Duration::ZERO;
+        self.reversed = false;
+        self.finished = false;
+    }
+
+    // moves to the next frame for the clip's mode, returns true once a Once clip has played out
+    fn advance(&mut self, animation: &Animation) -> bool {
+        let last = animation.frames.len().saturating_sub(1);
+        match animation.mode {
+            PlaybackMode::Loop => {
+                self.current_frame = if self.current_frame < last {
+                    self.current_frame + 1
+                } else {
+                    0
+                };
+            }
+            PlaybackMode::Once => {
+                if self.current_frame < last {
+                    self.current_frame += 1;
+                } else if !self.finished {
+                    self.finished = true;
+                    return true;
+                }
+            }
+            PlaybackMode::PingPong => {
+                if last == 0 {
+                    return false;
+                }
+                if self.current_frame == last {
+                    self.reversed = true;
+                } else if self.current_frame == 0 {
+                    self.reversed = false;
+                }
+                self.current_frame = if self.reversed {
+                    self.current_frame - 1
+                } else {
+                    self.current_frame + 1
+                };
+            }
+        }
+        false
+    }
+}
+
+pub fn apply_animation_intents(
+    mut intents: EventReader<AnimationIntent>,
+    mut query: Query<(&Animations, &mut Animator)>,
+) {
+    for intent in intents.iter() {
+        let Ok((animations, mut animator)) = query.get_mut(intent.entity) else {
+            continue;
+        };
+        let trigger = TransitionTrigger::Intent(intent.intent.to_string());
+        if let Some(next) = animations.next_animation(&animator.current_animation, &trigger) {
+            let next = next.to_string();
+            animator.play(&next);
+        }
+    }
+}
+
+fn show_frame(
+    entity: Entity,
+    frame: &AnimationFrame,
+    atlas: &mut Mut<Handle<TextureAtlas>>,
+    sprite: &mut Mut<TextureAtlasSprite>,
+    frame_events: &mut EventWriter<AnimationFrameEvent>,
+) {
+    // only touch the handle when it changes to keep change detection quiet
+    if **atlas != frame.atlas_handle {
+        **atlas = frame.atlas_handle.clone();
+    }
+    sprite.index = frame.atlas_index;
+    frame_events.send_batch(
+        frame
+            .events
+            .iter()
+            .map(|&event| AnimationFrameEvent { entity, event }),
+    );
 }
 
 pub fn sprite_animation_system(
     time: Res<Time>,
     mut query: Query<(
+        Entity,
         &Animations,
         &mut Animator,
         &mut Handle<TextureAtlas>,
         &mut TextureAtlasSprite,
     )>,
+    mut frame_events: EventWriter<AnimationFrameEvent>,
+    mut finished_events: EventWriter<AnimationFinished>,
 ) {
-    for (animations, mut animator, mut atlas, mut sprite) in &mut query.iter_mut() {
-        animator.timer.tick(time.delta());
+    for (entity, animations, mut animator, mut atlas, mut sprite) in query.iter_mut() {
+        let Some(mut animation) = animations.animations.get(&animator.current_animation) else {
+            continue;
+        };
 
-        if !animator.timer.finished() && animator.last_animation == animator.current_animation {
-            break;
+        // a played clip starts on the frame it was played from
+        if animator.restarted {
+            animator.restarted = false;
+            animator.frame_time = Duration::ZERO;
+            if let Some(frame) = animation.frames.get(animator.current_frame) {
+                show_frame(entity, frame, &mut atlas, &mut sprite, &mut frame_events);
+            }
+            continue;
         }
-        animator.last_animation = animator.current_animation;
 
-        if let Some(animation) = animations.animations.get(animator.current_animation) {
-            animator.current_frame = if animator.current_frame + 1 < animation.frames.len() {
-                animator.current_frame + 1
-            } else {
-                0
+        if animator.paused || animator.finished {
+            continue;
+        }
+        let delta = time.delta().mul_f32(animator.speed.max(0.0));
+        animator.frame_time += delta;
+
+        // long frames or fast playback can step over several frames in one update,
+        // the step limit keeps zero length frames from spinning forever
+        for _ in 0..animation.frames.len() {
+            let Some(duration) = animation
+                .frames
+                .get(animator.current_frame)
+                .map(|frame| frame.duration)
+            else {
+                break;
             };
+            if animator.frame_time < duration {
+                break;
+            }
+            animator.frame_time -= duration;
+
+            if animator.advance(animation) {
+                finished_events.send(AnimationFinished {
+                    entity,
+                    animation: animator.current_animation.clone(),
+                });
+                let trigger = TransitionTrigger::Finished;
+                let Some(next) = animations.next_animation(&animator.current_animation, &trigger)
+                else {
+                    break;
+                };
+                let Some(next_animation) = animations.animations.get(next) else {
+                    break;
+                };
+                let next = next.to_string();
+                animator.play(&next);
+                // its first frame is shown below
+                animator.restarted = false;
+                animation = next_animation;
+            }
 
             if let Some(frame) = animation.frames.get(animator.current_frame) {
-                animator.timer.set_duration(frame.duration);
-                animator.timer.reset();
-                *atlas = frame.atlas_handle.clone();
-                sprite.index = frame.atlas_index;
+                show_frame(entity, frame, &mut atlas, &mut sprite, &mut frame_events);
             }
         }
     }
 }
+
+pub struct AnimationPlugin;
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(RonAssetPlugin::<AnimationSet>::new(&["anim"]))
+            .add_event::<AnimationIntent>()
+            .add_event::<AnimationFrameEvent>()
+            .add_event::<AnimationFinished>()
+            .add_system(apply_animation_intents.before(sprite_animation_system))
+            .add_system(sprite_animation_system);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::event::Events;
+
+    fn animation(frames: usize, mode: PlaybackMode) -> Animation {
+        Animation {
+            frames: (0..frames)
+                .map(|atlas_index| AnimationFrame {
+                    atlas_handle: Handle::default(),
+                    atlas_index,
+                    duration: Duration::from_secs_f32(0.1),
+                    events: Vec::new(),
+                })
+                .collect(),
+            mode,
+        }
+    }
+
+    fn played_frames(animation: &Animation, steps: usize) -> Vec<usize> {
+        let mut animator = Animator::new("clip");
+        (0..steps)
+            .map(|_| {
+                animator.advance(animation);
+                animator.current_frame
+            })
+            .collect()
+    }
+
+    #[test]
+    fn playback_modes() {
+        assert_eq!(
+            played_frames(&animation(3, PlaybackMode::Loop), 5),
+            vec![1, 2, 0, 1, 2]
+        );
+        assert_eq!(
+            played_frames(&animation(3, PlaybackMode::Once), 5),
+            vec![1, 2, 2, 2, 2]
+        );
+        assert_eq!(
+            played_frames(&animation(3, PlaybackMode::PingPong), 6),
+            vec![1, 2, 1, 0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn once_finishes_a_single_time() {
+        let animation = animation(2, PlaybackMode::Once);
+        let mut animator = Animator::new("clip");
+        assert!(!animator.advance(&animation));
+        assert!(animator.advance(&animation));
+        assert!(!animator.advance(&animation));
+    }
+
+    #[test]
+    fn playing_the_same_clip_restarts_it() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .add_event::<AnimationFrameEvent>()
+            .add_event::<AnimationFinished>()
+            .add_system(sprite_animation_system);
+        let mut clip = animation(2, PlaybackMode::Loop);
+        clip.frames[0].events.push(FrameEvent::Footstep);
+        let entity = app
+            .world
+            .spawn((
+                Animations {
+                    animations: HashMap::from([("clip".to_string(), clip)]),
+                    transitions: Vec::new(),
+                },
+                Animator::new("clip"),
+                Handle::<TextureAtlas>::default(),
+                TextureAtlasSprite::new(1),
+            ))
+            .id();
+        let footsteps = |app: &App| app.world.resource::<Events<AnimationFrameEvent>>().len();
+
+        app.update();
+        assert_eq!(footsteps(&app), 1);
+        app.world
+            .get_mut::<TextureAtlasSprite>(entity)
+            .unwrap()
+            .index = 1;
+        app.world.get_mut::<Animator>(entity).unwrap().play("clip");
+        app.update();
+        assert_eq!(footsteps(&app), 2);
+        assert_eq!(
+            app.world.get::<TextureAtlasSprite>(entity).unwrap().index,
+            0
+        );
+    }
+
+    #[test]
+    fn transitions_name_real_clips() {
+        for path in ["assets/animations/shoe.anim", "assets/animations/suv.anim"] {
+            let set: AnimationSet = ron::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+            for transition in set.transitions.iter() {
+                let names = transition.from.iter().chain([&transition.to]);
+                for name in names {
+                    assert!(
+                        set.clips.contains_key(name),
+                        "{} has no clip {}",
+                        path,
+                        name
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn transitions_match_from_and_trigger() {
+        let animations = Animations {
+            animations: HashMap::new(),
+            transitions: vec![
+                Transition {
+                    from: Some("idle".to_string()),
+                    on: TransitionTrigger::Intent("move".to_string()),
+                    to: "walk".to_string(),
+                },
+                Transition {
+                    from: None,
+                    on: TransitionTrigger::Intent("hit".to_string()),
+                    to: "rolling".to_string(),
+                },
+            ],
+        };
+        let intent = |name: &str| TransitionTrigger::Intent(name.to_string());
+        assert_eq!(
+            animations.next_animation("idle", &intent("move")),
+            Some("walk")
+        );
+        assert_eq!(animations.next_animation("walk", &intent("move")), None);
+        assert_eq!(
+            animations.next_animation("walk", &intent("hit")),
+            Some("rolling")
+        );
+        assert_eq!(
+            animations.next_animation("idle", &TransitionTrigger::Finished),
+            None
+        );
+    }
+}
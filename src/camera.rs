@@ -0,0 +1,113 @@
+use crate::settings::Settings;
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// most the camera moves at full trauma, in game pixels
+const MAX_SHAKE: f32 = 3.0;
+// trauma lost per sec
+const TRAUMA_DECAY: f32 = 1.5;
+
+// sent by gameplay to shake the screen and freeze time for a moment
+pub struct Impact {
+    // 0 to 1, adds up with any shake that's already going
+    pub trauma: f32,
+    // secs of frozen time
+    pub hit_stop: f32,
+}
+
+// accessibility options, both on by default
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct CameraEffectsSettings {
+    pub screen_shake: bool,
+    pub hit_stop: bool,
+}
+impl Default for CameraEffectsSettings {
+    fn default() -> Self {
+        CameraEffectsSettings {
+            screen_shake: true,
+            hit_stop: true,
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct CameraShake {
+    trauma: f32,
+    // what was added to the camera last frame, so it can be taken back off
+    offset: Vec2,
+}
+
+#[derive(Resource, Default)]
+struct HitStop {
+    remaining: f32,
+}
+
+fn apply_impacts(
+    mut events: EventReader<Impact>,
+    mut cameras: Query<&mut CameraShake>,
+    mut hit_stop: ResMut<HitStop>,
+    mut time: ResMut<Time>,
+    settings: Res<Settings>,
+) {
+    let settings = &settings.camera_effects;
+    for impact in events.iter() {
+        if settings.screen_shake {
+            for mut shake in cameras.iter_mut() {
+                shake.trauma = (shake.trauma + impact.trauma).min(1.0);
+            }
+        }
+        if settings.hit_stop && impact.hit_stop > 0.0 {
+            hit_stop.remaining = hit_stop.remaining.max(impact.hit_stop);
+            time.pause();
+        }
+    }
+}
+
+// counts real time since game time is paused
+fn update_hit_stop(mut hit_stop: ResMut<HitStop>, mut time: ResMut<Time>) {
+    if hit_stop.remaining <= 0.0 {
+        return;
+    }
+    hit_stop.remaining -= time.raw_delta_seconds();
+    if hit_stop.remaining <= 0.0 {
+        time.unpause();
+    }
+}
+
+fn shake_camera(
+    mut cameras: Query<(&mut CameraShake, &mut Transform)>,
+    time: Res<Time>,
+    settings: Res<Settings>,
+) {
+    let settings = &settings.camera_effects;
+    let mut rng = rand::thread_rng();
+    for (mut shake, mut transform) in cameras.iter_mut() {
+        if !settings.screen_shake {
+            shake.trauma = 0.0;
+        }
+        // squared so small hits stay subtle
+        let amount = MAX_SHAKE * shake.trauma * shake.trauma;
+        // whole pixels only so the playfield stays on the pixel grid
+        let offset = Vec2::new(
+            rng.gen_range(-1.0..=1.0) * amount,
+            rng.gen_range(-1.0..=1.0) * amount,
+        )
+        .round();
+        transform.translation += (offset - shake.offset).extend(0.0);
+        shake.offset = offset;
+        shake.trauma = (shake.trauma - TRAUMA_DECAY * time.raw_delta_seconds()).max(0.0);
+    }
+}
+
+pub struct CameraEffectsPlugin;
+impl Plugin for CameraEffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HitStop>()
+            .add_event::<Impact>()
+            .add_system(apply_impacts)
+            .add_system(update_hit_stop.after(apply_impacts))
+            .add_system(shake_camera.after(apply_impacts));
+    }
+}
@@ -1,24 +1,36 @@
-use crate::collisions::Hitbox;
+use crate::animation::{AnimationFrameEvent, AnimationSet, Animations, Animator, FrameEvent};
+use crate::collisions::{CollisionLayers, Hitbox};
 use crate::consts::{AppState, SCREEN_X_MAX, SCREEN_Y_MAX, TILE_SIZE};
-use crate::coordinates::{Layer, PixelPosition, SpriteSize, TilePosition, Velocity};
+use crate::coordinates::{
+    Layer, PixelPosition, PreviousPixelPosition, SpriteSize, TilePosition, Velocity,
+};
 use crate::map::{load_current_map, CurrentLevel};
+use crate::mixer::AudioCategory;
+use crate::particles::{Emitter, EmitterConfig};
+use crate::positional_audio::PlaySoundAt;
 use crate::rng_bag::RngBag;
+use crate::shapes::ConvexPolygon;
 use bevy::prelude::*;
 
 #[derive(Component)]
 pub struct Car;
-struct GoingOffscreenEvent(Entity, f32, f32);
+// row and x velocity of a car leaving the screen
+struct GoingOffscreenEvent(f32, f32);
 
 #[derive(Clone, Default, Resource)]
 struct Materials {
     suv_material: Handle<TextureAtlas>,
+    suv_animations: Animations,
+    exhaust_emitter: Handle<EmitterConfig>,
 }
 
 #[derive(Resource)]
-struct ColorBag(pub RngBag<usize>);
+struct ColorBag(pub RngBag<&'static str>);
 impl Default for ColorBag {
     fn default() -> ColorBag {
-        ColorBag(RngBag::<usize>::new(vec![0, 0, 0, 1, 2, 3, 4, 5]))
+        ColorBag(RngBag::<&'static str>::new(vec![
+            "paint_0", "paint_0", "paint_0", "paint_1", "paint_2", "paint_3", "paint_4", "paint_5",
+        ]))
     }
 }
 
@@ -26,11 +38,14 @@ fn store_car_material(
     mut m: ResMut<Materials>,
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    animation_sets: Res<Assets<AnimationSet>>,
 ) {
-    let texture_handle = asset_server.load("sprites/suv.png");
-    let sprite_size = SpriteSize(Vec2::new(14.0, 8.0));
-    let texture_atlas = TextureAtlas::from_grid(texture_handle, sprite_size.0, 6, 1, None, None);
-    m.suv_material = texture_atlases.add(texture_atlas);
+    let animation_set = animation_sets
+        .get(&asset_server.get_handle("animations/suv.anim"))
+        .expect("suv animations should be loaded before the level");
+    m.suv_material = texture_atlases.add(animation_set.texture_atlas(&asset_server));
+    m.suv_animations = Animations::from_set(animation_set, m.suv_material.clone());
+    m.exhaust_emitter = asset_server.load("particles/exhaust.emitter");
 }
 
 #[derive(Bundle)]
@@ -40,9 +55,13 @@ struct CarBundle {
     car: Car,
     layer: Layer,
     pixel_position: PixelPosition,
+    previous_pixel_position: PreviousPixelPosition,
     velocity: Velocity,
     hitbox: Hitbox,
     sprite_size: SpriteSize,
+    animator: Animator,
+    animations: Animations,
+    emitter: Emitter,
 }
 
 fn spawn_car(
@@ -53,6 +72,10 @@ fn spawn_car(
     colors: &mut ColorBag,
 ) {
     let traveling_left = speed < 0.0;
+    let pixel_position = PixelPosition(Vec2::new(
+        tile_pos.0.x * TILE_SIZE as f32 + if traveling_left { 0.0 } else { 2.0 },
+        tile_pos.0.y * TILE_SIZE as f32,
+    ));
     commands.spawn(CarBundle {
         sprite_bundle: SpriteSheetBundle {
             texture_atlas: m.suv_material,
@@ -61,21 +84,28 @@ fn spawn_car(
                 translation: tile_pos.get_translation(Vec2::new(14.0, 8.0), 1.0),
                 ..Default::default()
             },
-            sprite: TextureAtlasSprite {
-                index: colors.0.get(),
-                ..Default::default()
-            },
             ..Default::default()
         },
         car: Car,
         sprite_size: SpriteSize(Vec2::new(14.0, 8.0)),
+        // each paint job is its own clip
+        animator: Animator::new(colors.0.get()),
+        animations: m.suv_animations,
         layer: Layer(1.0),
-        pixel_position: PixelPosition(Vec2::new(
-            tile_pos.0.x * TILE_SIZE as f32 + if traveling_left { 0.0 } else { 2.0 },
-            tile_pos.0.y * TILE_SIZE as f32,
-        )),
+        pixel_position,
+        previous_pixel_position: PreviousPixelPosition(pixel_position.0),
         velocity: Velocity(Vec2::new(speed, 0.0)),
-        hitbox: Hitbox::new(Vec2::new(0.0, 0.0), Vec2::new(14.0, 8.0)),
+        // cut the corners so clipping the bumper isn't a hit
+        hitbox: Hitbox::polygon(ConvexPolygon::rounded_box(
+            Vec2::new(0.0, 0.0),
+            Vec2::new(14.0, 8.0),
+            2.0,
+        ))
+        .with_layers(CollisionLayers::CAR, CollisionLayers::ALL),
+        // exhaust comes out the back bumper
+        emitter: Emitter::new(m.exhaust_emitter)
+            .with_offset(Vec2::new(if traveling_left { 14.0 } else { 0.0 }, 1.0))
+            .with_flip_x(traveling_left),
     });
 }
 
@@ -103,12 +133,12 @@ fn spawn_another_car(
     mut color_bag: ResMut<ColorBag>,
 ) {
     for ev in event_reader.iter() {
-        let spawn_x = if ev.2 < 0.0 { 16.0 } else { -2.0 };
+        let spawn_x = if ev.1 < 0.0 { 16.0 } else { -2.0 };
         spawn_car(
             &mut commands,
             m.clone(),
-            TilePosition(Vec2::new(spawn_x, ev.1)),
-            ev.2,
+            TilePosition(Vec2::new(spawn_x, ev.0)),
+            ev.1,
             &mut color_bag,
         );
     }
@@ -126,9 +156,9 @@ fn fully_offscreen(
 ) {
     for (entity, pos, hitbox, velocity) in q.iter_mut() {
         let left = pos.0.x;
-        let right = pos.0.x + hitbox.size.x;
+        let right = pos.0.x + hitbox.size().x;
         let top = pos.0.y;
-        let bottom = pos.0.y + hitbox.size.y;
+        let bottom = pos.0.y + hitbox.size().y;
         if (right < 0.0 && velocity.0.x < 0.0)
             || (left > SCREEN_X_MAX as f32 && velocity.0.x > 0.0)
             || (top < 0.0 && velocity.0.y < 0.0)
@@ -136,7 +166,6 @@ fn fully_offscreen(
         {
             commands.entity(entity).insert(FullyOffscreen);
             ev_going_offscreen.send(GoingOffscreenEvent(
-                entity,
                 pos.0.y / TILE_SIZE as f32,
                 velocity.0.x,
             ));
@@ -174,6 +203,26 @@ fn despawn_out_of_bounds(mut commands: Commands, mut q: Query<Entity, With<Fully
     }
 }
 
+// honk clips tag their frame, the sound comes from wherever the car is
+fn car_honk_sfx(
+    mut frame_events: EventReader<AnimationFrameEvent>,
+    car_query: Query<(&PixelPosition, &SpriteSize), With<Car>>,
+    mut sounds: EventWriter<PlaySoundAt>,
+) {
+    for ev in frame_events.iter() {
+        if ev.event != FrameEvent::Honk {
+            continue;
+        }
+        if let Ok((position, size)) = car_query.get(ev.entity) {
+            sounds.send(PlaySoundAt {
+                path: "sfx/honk.ogg",
+                category: AudioCategory::Sfx,
+                position: position.0 + size.0 / 2.0,
+            });
+        }
+    }
+}
+
 pub struct CarPlugin;
 impl Plugin for CarPlugin {
     fn build(&self, app: &mut App) {
@@ -187,13 +236,15 @@ impl Plugin for CarPlugin {
                 )
                     .in_schedule(OnEnter(AppState::Loading)),
             )
+            .add_system(car_honk_sfx.in_set(OnUpdate(AppState::InGame)))
             .add_systems(
                 (
                     fully_offscreen.before(spawn_another_car),
                     spawn_another_car,
                     despawn_out_of_bounds.after(fully_offscreen),
                 )
-                    .in_set(OnUpdate(AppState::InGame)),
+                    .distributive_run_if(in_state(AppState::InGame))
+                    .in_schedule(CoreSchedule::FixedUpdate),
             );
     }
 }
@@ -1,116 +1,389 @@
-use crate::car::Car;
 use crate::consts::{AppState, SystemLabels};
-use crate::coordinates::update_translation;
-use crate::map::Wall;
-use crate::player::Player;
-use bevy::{
-    prelude::*,
-    sprite::collide_aabb::{collide, Collision},
-};
+use crate::coordinates::{update_position, PixelPosition, PreviousPixelPosition, SpriteSize};
+use crate::shapes::{overlap, sweep, Box, Circle, Contact, ConvexPolygon, Shape, ToWorldShape};
+use crate::spatial_hash::SpatialHash;
+use bevy::{prelude::*, utils::HashMap};
 use std::marker::PhantomData;
 
-#[derive(Copy, Clone, PartialEq)]
-pub struct Box {
-    pub offset: Vec2, // position relative to parent
-    pub size: Vec2,
-}
-impl Default for Box {
-    fn default() -> Self {
-        Box {
-            offset: Vec2::new(0.0, 0.0),
-            size: Vec2::new(1.0, 1.0),
-        }
+// bitflags for the layers a box is on, or the layers it's allowed to touch
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct CollisionLayers(pub u32);
+impl CollisionLayers {
+    pub const NONE: CollisionLayers = CollisionLayers(0);
+    pub const PLAYER: CollisionLayers = CollisionLayers(1 << 0);
+    pub const CAR: CollisionLayers = CollisionLayers(1 << 1);
+    pub const WALL: CollisionLayers = CollisionLayers(1 << 2);
+    pub const TRIGGER: CollisionLayers = CollisionLayers(1 << 3);
+    pub const ALL: CollisionLayers = CollisionLayers(u32::MAX);
+
+    pub const fn union(self, other: CollisionLayers) -> CollisionLayers {
+        CollisionLayers(self.0 | other.0)
+    }
+
+    pub fn intersects(self, other: CollisionLayers) -> bool {
+        self.0 & other.0 != 0
     }
 }
 
+// boxes are on every layer and touch every layer unless told otherwise
 #[derive(Component)]
-pub struct Hurtbox(Box);
+pub struct Hurtbox {
+    shape: Shape,
+    pub layers: CollisionLayers,
+    pub mask: CollisionLayers,
+}
 impl Hurtbox {
     pub fn new(offset: Vec2, size: Vec2) -> Self {
-        Hurtbox(Box { offset, size })
+        Hurtbox::from_shape(Shape::Box(Box { offset, size }))
+    }
+
+    // no level uses round hurtboxes yet
+    #[allow(dead_code)]
+    pub fn circle(offset: Vec2, radius: f32) -> Self {
+        Hurtbox::from_shape(Shape::Circle(Circle { offset, radius }))
+    }
+
+    #[allow(dead_code)]
+    pub fn polygon(polygon: ConvexPolygon) -> Self {
+        Hurtbox::from_shape(Shape::ConvexPolygon(polygon))
+    }
+
+    fn from_shape(shape: Shape) -> Self {
+        Hurtbox {
+            shape,
+            layers: CollisionLayers::ALL,
+            mask: CollisionLayers::ALL,
+        }
+    }
+
+    pub fn with_layers(mut self, layers: CollisionLayers, mask: CollisionLayers) -> Self {
+        self.layers = layers;
+        self.mask = mask;
+        self
     }
 }
 impl std::ops::Deref for Hurtbox {
-    type Target = Box;
+    type Target = Shape;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.shape
     }
 }
 
 #[derive(Component)]
-pub struct Hitbox(Box);
+pub struct Hitbox {
+    shape: Shape,
+    pub layers: CollisionLayers,
+    pub mask: CollisionLayers,
+}
 impl Hitbox {
     pub fn new(offset: Vec2, size: Vec2) -> Self {
-        Hitbox(Box { offset, size })
+        Hitbox::from_shape(Shape::Box(Box { offset, size }))
+    }
+
+    // no level uses round hitboxes yet
+    #[allow(dead_code)]
+    pub fn circle(offset: Vec2, radius: f32) -> Self {
+        Hitbox::from_shape(Shape::Circle(Circle { offset, radius }))
+    }
+
+    pub fn polygon(polygon: ConvexPolygon) -> Self {
+        Hitbox::from_shape(Shape::ConvexPolygon(polygon))
+    }
+
+    fn from_shape(shape: Shape) -> Self {
+        Hitbox {
+            shape,
+            layers: CollisionLayers::ALL,
+            mask: CollisionLayers::ALL,
+        }
+    }
+
+    pub fn with_layers(mut self, layers: CollisionLayers, mask: CollisionLayers) -> Self {
+        self.layers = layers;
+        self.mask = mask;
+        self
     }
 }
 impl std::ops::Deref for Hitbox {
-    type Target = Box;
+    type Target = Shape;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.shape
     }
 }
 
+// both boxes have to accept the other's layers
+pub fn layers_interact(hurtbox: &Hurtbox, hitbox: &Hitbox) -> bool {
+    hurtbox.mask.intersects(hitbox.layers) && hitbox.mask.intersects(hurtbox.layers)
+}
+
 // systems detect collision between Hurtbox and Hitbox,
 // emit event that should be handled?
 // game has 3 types of collisions
 // 1. player collides with wall
 // 2. player collides with car
 // 3. player collides with goal
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CollisionPhase {
+    // first fixed step the pair overlaps
+    Started,
+    // pair was already overlapping last step
+    Ongoing,
+    // pair stopped overlapping or one of them despawned
+    Ended,
+}
+
 pub struct CollisionData {
-    collided_with: Entity,
-    collision: Collision,
+    pub entity: Entity,
+    pub collided_with: Entity,
+    // for Ended this is the last contact while overlapping
+    // depth is 0.0 if they passed through each other during the step
+    #[allow(dead_code)]
+    pub collision: Contact,
+    // fraction of the step when they first touched, 0.0 if already touching at the start
+    #[allow(dead_code)]
+    pub time_of_impact: f32,
+    pub phase: CollisionPhase,
 }
 pub struct CollisionEvent<S, T>(CollisionData, PhantomData<S>, PhantomData<T>);
 impl<S, T> CollisionEvent<S, T> {
-    fn new(data: CollisionData) -> Self {
+    pub(crate) fn new(data: CollisionData) -> Self {
         Self(data, PhantomData, PhantomData)
     }
 }
+impl<S, T> std::ops::Deref for CollisionEvent<S, T> {
+    type Target = CollisionData;
 
-fn collision_system(
-    hurtboxes: Query<(&Hurtbox, &Transform, Option<&Player>)>,
-    hitboxes: Query<(&Hitbox, &Transform, Entity, Option<&Car>, Option<&Wall>)>,
-    mut ev_player_hitby_car: EventWriter<CollisionEvent<Player, Car>>,
-    mut ev_player_hitby_wall: EventWriter<CollisionEvent<Player, Wall>>,
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+// pairs that overlapped last step, by hurtbox and hitbox entity
+#[derive(Resource)]
+struct Contacts<S, T>(HashMap<(Entity, Entity), Contact>, PhantomData<(S, T)>);
+impl<S, T> Default for Contacts<S, T> {
+    fn default() -> Self {
+        Contacts(HashMap::default(), PhantomData)
+    }
+}
+
+// centers at the start and end of the step, from where it actually was rather than its velocity,
+// so a position snapped by commands isn't swept as if it had travelled there
+fn step_centers(
+    pos: &PixelPosition,
+    previous: Option<&PreviousPixelPosition>,
+    sprite_size: &SpriteSize,
+) -> (Vec2, Vec2) {
+    let end = pos.get_translation(sprite_size.0, 0.0).truncate();
+    let start = previous.map_or(end, |previous| {
+        PixelPosition(previous.0)
+            .get_translation(sprite_size.0, 0.0)
+            .truncate()
+    });
+    (start, end)
+}
+
+// keep the hitbox broadphase in sync with hitboxes that spawned, moved or changed shape
+// moving hitboxes cover everything they passed through this step
+fn update_hitbox_hash(
+    mut hash: ResMut<SpatialHash>,
+    hitboxes: Query<
+        (
+            Entity,
+            &Hitbox,
+            &PixelPosition,
+            &SpriteSize,
+            Option<&PreviousPixelPosition>,
+        ),
+        Or<(Changed<PixelPosition>, Changed<Hitbox>)>,
+    >,
+) {
+    for (entity, hitbox, pos, sprite_size, previous) in hitboxes.iter() {
+        let (start, end) = step_centers(pos, previous, sprite_size);
+        let (min, max) = hitbox.to_world_shape(end).bounds();
+        let motion = end - start;
+        hash.update(entity, min.min(min - motion), max.max(max - motion));
+    }
+}
+
+// every frame rather than every fixed step, removals are only kept for a frame or two
+// and frames without a fixed step would miss them
+fn remove_despawned_hitboxes(
+    mut hash: ResMut<SpatialHash>,
+    mut removed: RemovedComponents<Hitbox>,
+) {
+    for entity in removed.iter() {
+        hash.remove(entity);
+    }
+}
+
+fn clear_hitbox_hash(mut hash: ResMut<SpatialHash>) {
+    hash.clear();
+}
+
+// checks each Hurtbox on an S against the Hitboxes on a T in nearby cells
+// and compares against last step's contacts to find which pairs started or ended
+// fast movers are swept over the step so they can't pass through each other
+fn collision_system<S: Component, T: Component>(
+    hash: Res<SpatialHash>,
+    hurtboxes: Query<
+        (
+            Entity,
+            &Hurtbox,
+            &PixelPosition,
+            &SpriteSize,
+            Option<&PreviousPixelPosition>,
+        ),
+        With<S>,
+    >,
+    hitboxes: Query<
+        (
+            &Hitbox,
+            &PixelPosition,
+            &SpriteSize,
+            Option<&PreviousPixelPosition>,
+        ),
+        With<T>,
+    >,
+    mut ev_collision: EventWriter<CollisionEvent<S, T>>,
+    mut contacts: ResMut<Contacts<S, T>>,
 ) {
-    for (hurtbox, hurt_transform, player) in hurtboxes.iter() {
-        let hurt_top_left = hurt_transform.translation + hurtbox.offset.extend(0.0);
-        let hurt_size = hurtbox.size;
-
-        for (hitbox, hit_transform, hit_entity, car, wall) in hitboxes.iter() {
-            let hit_top_left = hit_transform.translation + hitbox.offset.extend(0.0);
-            let hit_size = hitbox.size;
-
-            if let Some(collision) = collide(hurt_top_left, hurt_size, hit_top_left, hit_size) {
-                if player.is_some() && car.is_some() {
-                    ev_player_hitby_car.send(CollisionEvent::new(CollisionData {
-                        collided_with: hit_entity,
-                        collision,
-                    }));
-                } else if player.is_some() && wall.is_some() {
-                    ev_player_hitby_wall.send(CollisionEvent::new(CollisionData {
-                        collided_with: hit_entity,
-                        collision,
-                    }));
+    let mut previous_contacts = std::mem::take(&mut contacts.0);
+
+    for (hurt_entity, hurtbox, hurt_pos, hurt_sprite_size, hurt_previous) in hurtboxes.iter() {
+        let (hurt_start, hurt_center) = step_centers(hurt_pos, hurt_previous, hurt_sprite_size);
+        let hurt_shape = hurtbox.to_world_shape(hurt_center);
+        let (min, max) = hurt_shape.bounds();
+        let hurt_motion = hurt_center - hurt_start;
+
+        for hit_entity in hash.query(min.min(min - hurt_motion), max.max(max - hurt_motion)) {
+            // nearby entity isn't a T
+            let Ok((hitbox, hit_pos, hit_sprite_size, hit_previous)) = hitboxes.get(hit_entity)
+            else {
+                continue;
+            };
+            if !layers_interact(hurtbox, hitbox) {
+                continue;
+            }
+            let (hit_start, hit_center) = step_centers(hit_pos, hit_previous, hit_sprite_size);
+            let hit_shape = hitbox.to_world_shape(hit_center);
+            let (hit_min, hit_max) = hit_shape.bounds();
+            let hit_motion = hit_center - hit_start;
+
+            // sweep the bounds from where both were at the start of the step
+            let swept = sweep(
+                min - hurt_motion,
+                max - hurt_motion,
+                hurt_motion - hit_motion,
+                hit_min - hit_motion,
+                hit_max - hit_motion,
+            );
+            let collision = match overlap(&hurt_shape, &hit_shape) {
+                Some(contact) => Some((contact, swept.map_or(0.0, |hit| hit.time))),
+                None => {
+                    let bounds_overlap = min.cmplt(hit_max).all() && max.cmpgt(hit_min).all();
+                    // bounds crossed during the step and are apart again at the end.
+                    // circles and rounded corners can cross bounds without touching,
+                    // so the shapes themselves have to overlap halfway through the crossing
+                    swept
+                        .filter(|hit| hit.time > 0.0 && !bounds_overlap)
+                        .filter(|hit| {
+                            let t = (hit.time + hit.exit.min(1.0)) / 2.0;
+                            overlap(
+                                &hurtbox.to_world_shape(hurt_start + hurt_motion * t),
+                                &hitbox.to_world_shape(hit_start + hit_motion * t),
+                            )
+                            .is_some()
+                        })
+                        .map(|hit| {
+                            (
+                                Contact {
+                                    normal: hit.normal,
+                                    depth: 0.0,
+                                },
+                                hit.time,
+                            )
+                        })
                 }
+            };
+
+            if let Some((collision, time_of_impact)) = collision {
+                let phase = if previous_contacts
+                    .remove(&(hurt_entity, hit_entity))
+                    .is_some()
+                {
+                    CollisionPhase::Ongoing
+                } else {
+                    CollisionPhase::Started
+                };
+                contacts.0.insert((hurt_entity, hit_entity), collision);
+                ev_collision.send(CollisionEvent::new(CollisionData {
+                    entity: hurt_entity,
+                    collided_with: hit_entity,
+                    collision,
+                    time_of_impact,
+                    phase,
+                }));
             }
         }
     }
+
+    // anything left over didn't overlap this step
+    for ((entity, collided_with), collision) in previous_contacts {
+        ev_collision.send(CollisionEvent::new(CollisionData {
+            entity,
+            collided_with,
+            collision,
+            time_of_impact: 0.0,
+            phase: CollisionPhase::Ended,
+        }));
+    }
+}
+
+// so the next level doesn't start by ending contacts with entities that are gone
+fn clear_contacts<S: Component, T: Component>(mut contacts: ResMut<Contacts<S, T>>) {
+    contacts.0.clear();
+}
+
+pub trait AddCollisionPair {
+    // emit CollisionEvent<S, T> when a Hurtbox on an S overlaps a Hitbox on a T
+    fn add_collision_pair<S: Component, T: Component>(&mut self) -> &mut Self;
+}
+impl AddCollisionPair for App {
+    fn add_collision_pair<S: Component, T: Component>(&mut self) -> &mut Self {
+        self.add_event::<CollisionEvent<S, T>>()
+            .init_resource::<Contacts<S, T>>()
+            .add_system(
+                collision_system::<S, T>
+                    .in_set(SystemLabels::Collisions)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(clear_contacts::<S, T>.in_schedule(OnExit(AppState::InGame)))
+    }
 }
 
 pub struct CollisionPlugin;
 impl Plugin for CollisionPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<CollisionEvent<Player, Car>>()
-            .add_event::<CollisionEvent<Player, Wall>>()
+        app.init_resource::<SpatialHash>()
             .add_system(
-                collision_system
-                    .in_set(OnUpdate(AppState::InGame))
-                    .after(update_translation)
-                    .before(SystemLabels::PlayerMovement),
-            );
+                update_hitbox_hash
+                    .run_if(in_state(AppState::InGame))
+                    .after(update_position)
+                    .before(SystemLabels::Collisions)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(remove_despawned_hitboxes)
+            .add_system(clear_hitbox_hash.in_schedule(OnExit(AppState::InGame)))
+            .edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+                schedule.configure_set(
+                    SystemLabels::Collisions
+                        .run_if(in_state(AppState::InGame))
+                        .after(update_position)
+                        .before(SystemLabels::PlayerMovement),
+                );
+            });
     }
 }
@@ -14,11 +14,15 @@ pub enum AppState {
 #[derive(Debug, Hash, Clone, Eq, PartialEq, SystemSet)]
 pub enum SystemLabels {
     PlayerMovement,
+    Collisions,
 }
 
 pub const TILE_WIDTH: f32 = 16.0;
 pub const TILE_HEIGHT: f32 = 16.0;
 pub const SCALE: f32 = 4.0;
 pub const TILE_SIZE: i32 = 8;
-pub const SCREEN_X_MAX: i32 = TILE_WIDTH as i32 * TILE_SIZE as i32;
-pub const SCREEN_Y_MAX: i32 = TILE_HEIGHT as i32 * TILE_SIZE as i32;
+pub const SCREEN_X_MAX: i32 = TILE_WIDTH as i32 * TILE_SIZE;
+pub const SCREEN_Y_MAX: i32 = TILE_HEIGHT as i32 * TILE_SIZE;
+
+// gameplay runs at a fixed rate so collisions don't depend on frame rate
+pub const FIXED_TIMESTEP: f32 = 1.0 / 60.0;
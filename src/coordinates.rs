@@ -1,4 +1,4 @@
-use crate::consts::{AppState, TILE_SIZE};
+use crate::consts::{AppState, SystemLabels, TILE_SIZE};
 use bevy::prelude::*;
 use bevy::sprite::Sprite;
 use bevy::transform::components::Transform;
@@ -13,6 +13,9 @@ pub struct Acceleration(pub Vec2);
 pub struct SpriteSize(pub Vec2);
 #[derive(Component)]
 pub struct Layer(pub f32);
+// sprites that outlive a level, like pooled particles and transition overlays
+#[derive(Component)]
+pub struct KeepBetweenLevels;
 
 #[derive(Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TilePosition(pub Vec2);
@@ -40,32 +43,71 @@ impl PixelPosition {
         // TODO: round is probably incorrect if sprite has odd (1, 3, 5, ...) dimensions
         (self.0 + size / 2.).round().extend(z)
     }
+
+    // position between the last two fixed steps, alpha is how far into the next step we are
+    pub fn interpolate(&self, previous: &PreviousPixelPosition, alpha: f32) -> PixelPosition {
+        PixelPosition(previous.0.lerp(self.0, alpha.clamp(0.0, 1.0)))
+    }
+}
+
+// pixel position at the start of the current fixed step, used to smooth rendering
+#[derive(Default, Copy, Clone, PartialEq, Component)]
+pub struct PreviousPixelPosition(pub Vec2);
+
+fn store_previous_position(mut q: Query<(&PixelPosition, &mut PreviousPixelPosition)>) {
+    for (p, mut previous) in q.iter_mut() {
+        previous.0 = p.0;
+    }
 }
 
-fn update_velocity(mut q: Query<(&Acceleration, &mut Velocity)>, time: Res<Time>) {
+fn update_velocity(mut q: Query<(&Acceleration, &mut Velocity)>, time: Res<FixedTime>) {
     for (a, mut v) in q.iter_mut() {
-        v.0 += a.0 * time.delta_seconds();
+        v.0 += a.0 * time.period.as_secs_f32();
     }
 }
 
-fn update_position(mut q: Query<(&Velocity, &mut PixelPosition)>, time: Res<Time>) {
+pub fn update_position(mut q: Query<(&Velocity, &mut PixelPosition)>, time: Res<FixedTime>) {
     for (v, mut p) in q.iter_mut() {
-        p.0 += v.0 * time.delta_seconds();
+        p.0 += v.0 * time.period.as_secs_f32();
     }
 }
 
+pub fn interpolation_alpha(time: &FixedTime) -> f32 {
+    time.accumulated().as_secs_f32() / time.period.as_secs_f32()
+}
+
 // TODO: add Changed<PixelPosition> here after upgrading to 0.5
 // TODO: figure out how to unify update_translation and update_translation_atlas_sprite
-pub fn update_translation(mut q: Query<(&PixelPosition, &Sprite, &mut Transform, &Layer)>) {
-    for (pos, sprite, mut transform, layer) in q.iter_mut() {
+pub fn update_translation(
+    mut q: Query<(
+        &PixelPosition,
+        Option<&PreviousPixelPosition>,
+        &Sprite,
+        &mut Transform,
+        &Layer,
+    )>,
+    time: Res<FixedTime>,
+) {
+    let alpha = interpolation_alpha(&time);
+    for (pos, previous, sprite, mut transform, layer) in q.iter_mut() {
+        let pos = previous.map_or(*pos, |previous| pos.interpolate(previous, alpha));
         transform.translation = pos.get_translation(sprite.custom_size.unwrap(), layer.0);
     }
 }
 
 fn update_translation_atlas_sprite(
-    mut q: Query<(&PixelPosition, &SpriteSize, &mut Transform, &Layer)>,
+    mut q: Query<(
+        &PixelPosition,
+        Option<&PreviousPixelPosition>,
+        &SpriteSize,
+        &mut Transform,
+        &Layer,
+    )>,
+    time: Res<FixedTime>,
 ) {
-    for (pos, size, mut transform, layer) in q.iter_mut() {
+    let alpha = interpolation_alpha(&time);
+    for (pos, previous, size, mut transform, layer) in q.iter_mut() {
+        let pos = previous.map_or(*pos, |previous| pos.interpolate(previous, alpha));
         transform.translation = pos.get_translation(size.0, layer.0);
     }
 }
@@ -75,11 +117,15 @@ impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             (
+                store_previous_position.before(update_velocity),
                 update_velocity.before(update_position),
-                update_position,
-                update_translation.after(update_position),
-                update_translation_atlas_sprite.after(update_position),
+                update_position.before(SystemLabels::PlayerMovement),
             )
+                .distributive_run_if(in_state(AppState::InGame))
+                .in_schedule(CoreSchedule::FixedUpdate),
+        )
+        .add_systems(
+            (update_translation, update_translation_atlas_sprite)
                 .in_set(OnUpdate(AppState::InGame)),
         );
     }
@@ -118,6 +164,16 @@ mod tests {
     //     assert_eq!(p.0, Vec2::new(-0.5, -1.0));
     // }
 
+    #[test]
+    fn pixel_position_interpolate() {
+        let previous = PreviousPixelPosition(Vec2::new(0.0, 4.0));
+        let p = PixelPosition(Vec2::new(2.0, 8.0));
+
+        assert_eq!(p.interpolate(&previous, 0.0).0, Vec2::new(0.0, 4.0));
+        assert_eq!(p.interpolate(&previous, 0.5).0, Vec2::new(1.0, 6.0));
+        assert_eq!(p.interpolate(&previous, 1.5).0, Vec2::new(2.0, 8.0));
+    }
+
     #[test]
     fn pixel_position_get_transform() {
         let s = Sprite {
@@ -0,0 +1,219 @@
+// press F1 to draw hitboxes, hurtboxes and the tile grid
+// outlines are plain sprites so they show up in anything that renders the world
+use crate::collisions::{layers_interact, Hitbox, Hurtbox};
+use crate::consts::{SCREEN_X_MAX, SCREEN_Y_MAX, TILE_SIZE};
+use crate::coordinates::{
+    interpolation_alpha, KeepBetweenLevels, PixelPosition, PreviousPixelPosition, SpriteSize,
+};
+use crate::shapes::{overlap, ToWorldShape, WorldShape};
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+
+const GRID_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.2);
+const HURTBOX_COLOR: Color = Color::rgb(0.0, 1.0, 0.0);
+const HITBOX_COLOR: Color = Color::rgb(0.0, 0.6, 1.0);
+const COLLIDING_COLOR: Color = Color::rgb(1.0, 0.0, 1.0);
+const DEBUG_LAYER: f32 = 10.0;
+// one game pixel wide, the game is drawn at native resolution before upscaling
+const LINE_WIDTH: f32 = 1.0;
+const CIRCLE_SEGMENTS: usize = 12;
+
+#[derive(Default, Resource)]
+pub struct CollisionDebug {
+    pub enabled: bool,
+}
+
+#[derive(Component)]
+struct GridLine;
+
+// outlines are pooled, lines left over in a frame are hidden rather than despawned
+#[derive(Component)]
+struct DebugLine;
+
+fn toggle_collision_debug(keyboard_input: Res<Input<KeyCode>>, mut debug: ResMut<CollisionDebug>) {
+    if keyboard_input.just_pressed(KeyCode::F1) {
+        debug.enabled = !debug.enabled;
+    }
+}
+
+fn set_line(sprite: &mut Sprite, transform: &mut Transform, start: Vec2, end: Vec2, color: Color) {
+    let diff = end - start;
+    sprite.color = color;
+    sprite.custom_size = Some(Vec2::new(diff.length(), LINE_WIDTH));
+    transform.translation = ((start + end) / 2.0).extend(DEBUG_LAYER);
+    transform.rotation = Quat::from_rotation_z(diff.y.atan2(diff.x));
+}
+
+fn line_sprite(start: Vec2, end: Vec2, color: Color, visibility: Visibility) -> SpriteBundle {
+    let mut bundle = SpriteBundle {
+        visibility,
+        ..Default::default()
+    };
+    set_line(&mut bundle.sprite, &mut bundle.transform, start, end, color);
+    bundle
+}
+
+fn outline(shape: &WorldShape) -> Vec<Vec2> {
+    match shape {
+        WorldShape::Circle { center, radius } => (0..CIRCLE_SEGMENTS)
+            .map(|i| *center + Vec2::from_angle(i as f32 * TAU / CIRCLE_SEGMENTS as f32) * *radius)
+            .collect(),
+        WorldShape::Polygon(points) => points.clone(),
+    }
+}
+
+// offset moves the outline from the fixed step position to where the sprite is drawn
+fn outline_lines(
+    shape: &WorldShape,
+    offset: Vec2,
+    color: Color,
+    lines: &mut Vec<(Vec2, Vec2, Color)>,
+) {
+    let points = outline(shape);
+    for (start, end) in points.iter().zip(points.iter().cycle().skip(1)) {
+        lines.push((*start + offset, *end + offset, color));
+    }
+}
+
+// the same interpolation update_translation uses, so outlines stay on their sprites
+fn draw_offset(pos: &PixelPosition, previous: Option<&PreviousPixelPosition>, alpha: f32) -> Vec2 {
+    previous.map_or(Vec2::ZERO, |previous| {
+        pos.interpolate(previous, alpha).0 - pos.0
+    })
+}
+
+// the grid never changes, so it's spawned once and only shown or hidden
+fn spawn_grid(mut commands: Commands) {
+    let mut spawn = |start: Vec2, end: Vec2| {
+        commands.spawn((
+            line_sprite(start, end, GRID_COLOR, Visibility::Hidden),
+            GridLine,
+            KeepBetweenLevels,
+        ));
+    };
+    for x in (0..=SCREEN_X_MAX).step_by(TILE_SIZE as usize) {
+        let x = x as f32;
+        spawn(Vec2::new(x, 0.0), Vec2::new(x, SCREEN_Y_MAX as f32));
+    }
+    for y in (0..=SCREEN_Y_MAX).step_by(TILE_SIZE as usize) {
+        let y = y as f32;
+        spawn(Vec2::new(0.0, y), Vec2::new(SCREEN_X_MAX as f32, y));
+    }
+}
+
+fn draw_collision_debug(
+    mut commands: Commands,
+    debug: Res<CollisionDebug>,
+    mut grid: Query<&mut Visibility, (With<GridLine>, Without<DebugLine>)>,
+    mut pool: Query<(&mut Sprite, &mut Transform, &mut Visibility), With<DebugLine>>,
+    hurtboxes: Query<(
+        &Hurtbox,
+        &PixelPosition,
+        &SpriteSize,
+        Option<&PreviousPixelPosition>,
+    )>,
+    hitboxes: Query<(
+        &Hitbox,
+        &PixelPosition,
+        &SpriteSize,
+        Option<&PreviousPixelPosition>,
+    )>,
+    time: Res<FixedTime>,
+) {
+    let grid_visibility = if debug.enabled {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    for mut visibility in grid.iter_mut() {
+        if *visibility != grid_visibility {
+            *visibility = grid_visibility;
+        }
+    }
+
+    let mut lines = Vec::new();
+    if debug.enabled {
+        // overlaps are checked where the collision system sees the shapes
+        let alpha = interpolation_alpha(&time);
+        let hurt_shapes: Vec<(&Hurtbox, WorldShape, Vec2)> = hurtboxes
+            .iter()
+            .map(|(hurtbox, pos, size, previous)| {
+                let center = pos.get_translation(size.0, 0.0).truncate();
+                (
+                    hurtbox,
+                    hurtbox.to_world_shape(center),
+                    draw_offset(pos, previous, alpha),
+                )
+            })
+            .collect();
+        let hit_shapes: Vec<(&Hitbox, WorldShape, Vec2)> = hitboxes
+            .iter()
+            .map(|(hitbox, pos, size, previous)| {
+                let center = pos.get_translation(size.0, 0.0).truncate();
+                (
+                    hitbox,
+                    hitbox.to_world_shape(center),
+                    draw_offset(pos, previous, alpha),
+                )
+            })
+            .collect();
+
+        let mut hit_colliding = vec![false; hit_shapes.len()];
+        for (hurtbox, hurt_shape, offset) in hurt_shapes.iter() {
+            let mut colliding = false;
+            for (i, (hitbox, hit_shape, _)) in hit_shapes.iter().enumerate() {
+                if layers_interact(hurtbox, hitbox) && overlap(hurt_shape, hit_shape).is_some() {
+                    colliding = true;
+                    hit_colliding[i] = true;
+                }
+            }
+            let color = if colliding {
+                COLLIDING_COLOR
+            } else {
+                HURTBOX_COLOR
+            };
+            outline_lines(hurt_shape, *offset, color, &mut lines);
+        }
+        for ((_, hit_shape, offset), colliding) in hit_shapes.iter().zip(hit_colliding) {
+            let color = if colliding {
+                COLLIDING_COLOR
+            } else {
+                HITBOX_COLOR
+            };
+            outline_lines(hit_shape, *offset, color, &mut lines);
+        }
+    }
+
+    let mut lines = lines.into_iter();
+    for (mut sprite, mut transform, mut visibility) in pool.iter_mut() {
+        match lines.next() {
+            Some((start, end, color)) => {
+                set_line(&mut sprite, &mut transform, start, end, color);
+                *visibility = Visibility::Inherited;
+            }
+            None => {
+                if *visibility != Visibility::Hidden {
+                    *visibility = Visibility::Hidden;
+                }
+            }
+        }
+    }
+    // the pool grows to the most lines needed in a frame and stays that size
+    for (start, end, color) in lines {
+        commands.spawn((
+            line_sprite(start, end, color, Visibility::Inherited),
+            DebugLine,
+            KeepBetweenLevels,
+        ));
+    }
+}
+
+pub struct CollisionDebugPlugin;
+impl Plugin for CollisionDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CollisionDebug>()
+            .add_startup_system(spawn_grid)
+            .add_system(toggle_collision_debug)
+            .add_system(draw_collision_debug.after(toggle_collision_debug));
+    }
+}
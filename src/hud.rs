@@ -0,0 +1,144 @@
+use crate::consts::{AppState, SCREEN_X_MAX, SCREEN_Y_MAX, TILE_SIZE};
+use crate::localization::Localization;
+use crate::map::Levels;
+use crate::stats::LevelStats;
+use crate::transitions::screen_covered;
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+
+// drawn in the game screen so it's upscaled with the pixels around it,
+// under the transition overlays
+const HUD_Z: f32 = 40.0;
+// the font's native size, one game pixel per font pixel
+const FONT_SIZE: f32 = 8.0;
+const STRIP_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.5);
+
+#[derive(Component)]
+struct Hud;
+
+#[derive(Component, Clone, Copy)]
+enum HudText {
+    Level,
+    Lives,
+    Time,
+    Hits,
+}
+
+// m:ss
+fn format_time(secs: f32) -> String {
+    let secs = secs as u32;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+fn spawn_hud(mut commands: Commands, localization: Res<Localization>) {
+    let screen = Vec2::new(SCREEN_X_MAX as f32, SCREEN_Y_MAX as f32);
+    let strip = TILE_SIZE as f32;
+    let style = TextStyle {
+        font: localization.font(),
+        font_size: FONT_SIZE,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn((SpatialBundle::default(), Hud))
+        .with_children(|parent| {
+            // the top and bottom rows are kept clear of houses and bus stops,
+            // player_input keeps the player out of them too
+            for y in [strip / 2.0, screen.y - strip / 2.0] {
+                parent.spawn(SpriteBundle {
+                    sprite: Sprite {
+                        color: STRIP_COLOR,
+                        custom_size: Some(Vec2::new(screen.x, strip)),
+                        ..Default::default()
+                    },
+                    transform: Transform::from_xyz(screen.x / 2.0, y, HUD_Z),
+                    ..Default::default()
+                });
+            }
+
+            // whole pixel corners, anchored so text grows away from the edges
+            for (kind, anchor, position) in [
+                (HudText::Level, Anchor::TopLeft, Vec2::new(1.0, screen.y)),
+                (HudText::Lives, Anchor::BottomLeft, Vec2::new(1.0, 1.0)),
+                (
+                    HudText::Time,
+                    Anchor::BottomCenter,
+                    Vec2::new(screen.x / 2.0, 1.0),
+                ),
+                (
+                    HudText::Hits,
+                    Anchor::BottomRight,
+                    Vec2::new(screen.x - 1.0, 1.0),
+                ),
+            ] {
+                parent.spawn((
+                    Text2dBundle {
+                        text: Text::from_section("", style.clone()),
+                        text_anchor: anchor,
+                        transform: Transform::from_translation(position.extend(HUD_Z + 1.0)),
+                        ..Default::default()
+                    },
+                    kind,
+                ));
+            }
+        });
+}
+
+fn update_hud(
+    levels: Res<Levels>,
+    stats: Res<LevelStats>,
+    localization: Res<Localization>,
+    mut texts: Query<(&HudText, &mut Text)>,
+) {
+    let font = localization.font();
+    for (kind, mut text) in texts.iter_mut() {
+        let value = match kind {
+            HudText::Level => localization.format(
+                "hud.level",
+                &[
+                    ("number", &(levels.current_level + 1)),
+                    (
+                        "name",
+                        &localization.level_name(&levels, levels.current_level),
+                    ),
+                ],
+            ),
+            HudText::Lives => localization.format("hud.lives", &[("lives", &stats.lives)]),
+            HudText::Time => format_time(stats.elapsed),
+            HudText::Hits => localization.format("hud.hits", &[("hits", &stats.hits)]),
+        };
+        // only on change so the text isn't laid out again every frame
+        if text.sections[0].value != value || text.sections[0].style.font != font {
+            text.sections[0].value = value;
+            text.sections[0].style.font = font.clone();
+        }
+    }
+}
+
+fn despawn_hud(mut commands: Commands, query: Query<Entity, With<Hud>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub struct HudPlugin;
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(spawn_hud.in_schedule(OnEnter(AppState::InGame)))
+            .add_system(update_hud.in_set(OnUpdate(AppState::InGame)))
+            // stays up until the transition hides it instead of popping out as the level ends
+            .add_system(despawn_hud.run_if(screen_covered));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_format() {
+        assert_eq!(format_time(0.0), "0:00");
+        assert_eq!(format_time(9.9), "0:09");
+        assert_eq!(format_time(125.0), "2:05");
+    }
+}
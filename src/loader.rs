@@ -1,4 +1,5 @@
 use crate::consts::AppState;
+use crate::localization::{BOLD_FONT, FALLBACK_BOLD_FONT, FALLBACK_FONT, FONT, LANGUAGES};
 use bevy::asset::LoadState;
 use bevy::prelude::*;
 
@@ -12,18 +13,36 @@ struct MapHandles {
     handles: Vec<HandleUntyped>,
 }
 
+#[derive(Default, Resource)]
+struct AnimationHandles {
+    handles: Vec<HandleUntyped>,
+}
+
+#[derive(Default, Resource)]
+struct EmitterHandles {
+    handles: Vec<HandleUntyped>,
+}
+
 #[derive(Default, Resource)]
 struct SfxHandles {
     handles: Vec<HandleUntyped>,
 }
 
+#[derive(Default, Resource)]
+struct TextHandles {
+    handles: Vec<HandleUntyped>,
+}
+
 fn setup_loader(
     asset_server: Res<AssetServer>,
     mut sprite_handles: ResMut<SpriteHandles>,
     mut map_handles: ResMut<MapHandles>,
+    mut animation_handles: ResMut<AnimationHandles>,
+    mut emitter_handles: ResMut<EmitterHandles>,
     mut sfx_handles: ResMut<SfxHandles>,
+    mut text_handles: ResMut<TextHandles>,
 ) {
-    sprite_handles.handles = vec![
+    sprite_handles.handles = [
         "sprites/bus_stop.png",
         "sprites/house.png",
         "sprites/map_tiles.png",
@@ -35,7 +54,7 @@ fn setup_loader(
     .map(|filename| asset_server.load_untyped(*filename))
     .collect();
 
-    map_handles.handles = vec![
+    map_handles.handles = [
         "levels/2_slow_cars.map",
         "levels/4_cars.map",
         "levels/4_faster_cars.map",
@@ -48,17 +67,43 @@ fn setup_loader(
     .map(|filename| asset_server.load_untyped(*filename))
     .collect();
 
-    sfx_handles.handles = vec!["sfx/honk.ogg", "sfx/step.ogg"]
+    animation_handles.handles = ["animations/shoe.anim", "animations/suv.anim"]
         .iter()
         .map(|filename| asset_server.load_untyped(*filename))
         .collect();
+
+    emitter_handles.handles = [
+        "particles/blood.emitter",
+        "particles/dust.emitter",
+        "particles/exhaust.emitter",
+    ]
+    .iter()
+    .map(|filename| asset_server.load_untyped(*filename))
+    .collect();
+
+    sfx_handles.handles = ["sfx/engine.wav", "sfx/honk.ogg", "sfx/step.ogg"]
+        .iter()
+        .map(|filename| asset_server.load_untyped(*filename))
+        .collect();
+
+    // every language, so switching one in the settings is instant
+    text_handles.handles = LANGUAGES
+        .iter()
+        .map(|language| language.path())
+        .chain([FONT, BOLD_FONT, FALLBACK_FONT, FALLBACK_BOLD_FONT])
+        .map(|filename| asset_server.load_untyped(filename))
+        .collect();
 }
 
+#[allow(clippy::too_many_arguments)]
 fn track_assets_ready(
     mut state: ResMut<NextState<AppState>>,
     sprite_handles: Res<SpriteHandles>,
     map_handles: Res<MapHandles>,
+    animation_handles: Res<AnimationHandles>,
+    emitter_handles: Res<EmitterHandles>,
     sfx_handles: Res<SfxHandles>,
+    text_handles: Res<TextHandles>,
     asset_server: Res<AssetServer>,
 ) {
     let handles: Vec<HandleUntyped> = sprite_handles
@@ -66,7 +111,10 @@ fn track_assets_ready(
         .iter()
         .cloned()
         .chain(map_handles.handles.iter().cloned())
+        .chain(animation_handles.handles.iter().cloned())
+        .chain(emitter_handles.handles.iter().cloned())
         .chain(sfx_handles.handles.iter().cloned())
+        .chain(text_handles.handles.iter().cloned())
         .collect();
 
     if LoadState::Loaded
@@ -83,7 +131,10 @@ impl Plugin for AssetsLoadingPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<SpriteHandles>()
             .init_resource::<MapHandles>()
+            .init_resource::<AnimationHandles>()
+            .init_resource::<EmitterHandles>()
             .init_resource::<SfxHandles>()
+            .init_resource::<TextHandles>()
             .add_system(setup_loader.in_schedule(OnEnter(AppState::AssetLoading)))
             .add_system(track_assets_ready.in_set(OnUpdate(AppState::AssetLoading)));
     }
@@ -0,0 +1,233 @@
+use crate::map::Levels;
+use crate::settings::Settings;
+use ab_glyph::Font as _;
+use bevy::{prelude::*, reflect::TypeUuid};
+use bevy_common_assets::ron::RonAssetPlugin;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+
+pub const FONT: &str = "fonts/silkscreen/slkscr.ttf";
+pub const BOLD_FONT: &str = "fonts/silkscreen/slkscreb.ttf";
+// silkscreen only has plain ascii, anything else is drawn with these
+pub const FALLBACK_FONT: &str = "fonts/dejavu/DejaVuSans.ttf";
+pub const FALLBACK_BOLD_FONT: &str = "fonts/dejavu/DejaVuSans-Bold.ttf";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub enum Language {
+    #[default]
+    English,
+    Spanish,
+    Russian,
+}
+pub const LANGUAGES: [Language; 3] = [Language::English, Language::Spanish, Language::Russian];
+impl Language {
+    pub fn path(&self) -> &'static str {
+        match self {
+            Language::English => "locales/en.locale",
+            Language::Spanish => "locales/es.locale",
+            Language::Russian => "locales/ru.locale",
+        }
+    }
+
+    // direction is -1 or 1, wrapping around the list
+    pub fn cycle(&self, direction: i32) -> Language {
+        let index = LANGUAGES
+            .iter()
+            .position(|language| language == self)
+            .unwrap_or(0);
+        let count = LANGUAGES.len() as i32;
+        LANGUAGES[(index as i32 + direction).rem_euclid(count) as usize]
+    }
+}
+
+// one language's text by key, values can have {named} arguments
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "7d1e0b52-4c8a-4f31-9a3e-2b6f0c9d8e14"]
+pub struct Locale {
+    // in the language itself, for the settings menu
+    pub name: String,
+    pub strings: HashMap<String, String>,
+}
+
+#[derive(Resource)]
+pub struct Localization {
+    language: Language,
+    name: String,
+    strings: HashMap<String, String>,
+    // english, for keys a translation hasn't caught up with
+    fallback: HashMap<String, String>,
+    locales: HashMap<Language, Handle<Locale>>,
+    fonts: [Handle<Font>; 2],
+    fallback_fonts: [Handle<Font>; 2],
+    use_fallback_fonts: bool,
+}
+impl FromWorld for Localization {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        Localization {
+            language: Language::English,
+            name: String::new(),
+            strings: HashMap::new(),
+            fallback: HashMap::new(),
+            locales: LANGUAGES
+                .iter()
+                .map(|language| (*language, asset_server.load(language.path())))
+                .collect(),
+            fonts: [asset_server.load(FONT), asset_server.load(BOLD_FONT)],
+            fallback_fonts: [
+                asset_server.load(FALLBACK_FONT),
+                asset_server.load(FALLBACK_BOLD_FONT),
+            ],
+            use_fallback_fonts: false,
+        }
+    }
+}
+impl Localization {
+    // missing keys show up as themselves so they're easy to spot
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings
+            .get(key)
+            .or_else(|| self.fallback.get(key))
+            .map_or(key, |text| text.as_str())
+    }
+
+    pub fn format(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        args.iter()
+            .fold(self.get(key).to_string(), |text, (name, value)| {
+                text.replace(&format!("{{{}}}", name), &value.to_string())
+            })
+    }
+
+    // levels without a translation keep the name from their file
+    pub fn level_name(&self, levels: &Levels, index: usize) -> String {
+        let key = format!("level.{}", levels.level_stem(index));
+        self.strings
+            .get(&key)
+            .or_else(|| self.fallback.get(&key))
+            .cloned()
+            .unwrap_or_else(|| levels.level_name(index))
+    }
+
+    pub fn language_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn font(&self) -> Handle<Font> {
+        self.fonts(0)
+    }
+
+    pub fn bold_font(&self) -> Handle<Font> {
+        self.fonts(1)
+    }
+
+    fn fonts(&self, index: usize) -> Handle<Font> {
+        if self.use_fallback_fonts {
+            self.fallback_fonts[index].clone()
+        } else {
+            self.fonts[index].clone()
+        }
+    }
+}
+
+fn covers(font: &Font, text: &str) -> bool {
+    text.chars()
+        .all(|c| c.is_whitespace() || font.font.glyph_id(c).0 != 0)
+}
+
+// runs when the language changes, and again as locales and fonts finish loading or are edited
+fn update_localization(
+    mut localization: ResMut<Localization>,
+    settings: Res<Settings>,
+    locales: Res<Assets<Locale>>,
+    fonts: Res<Assets<Font>>,
+    mut locale_events: EventReader<AssetEvent<Locale>>,
+    mut font_events: EventReader<AssetEvent<Font>>,
+) {
+    let loaded = locale_events.iter().count() + font_events.iter().count() > 0;
+    if !loaded && settings.language == localization.language {
+        return;
+    }
+
+    let locale = |language: Language| locales.get(&localization.locales[&language]);
+    let english = locale(Language::English).map(|locale| locale.strings.clone());
+    let translation = locale(settings.language);
+    let pixel_font = fonts.get(&localization.fonts[0]);
+
+    localization.language = settings.language;
+    localization.fallback = english.unwrap_or_default();
+    let Some(locale) = translation else {
+        localization.name = String::new();
+        localization.strings = HashMap::new();
+        return;
+    };
+    localization.name = locale.name.clone();
+    localization.strings = locale.strings.clone();
+
+    // a language gets one font for all of its text, so screens don't mix the two
+    localization.use_fallback_fonts = pixel_font.is_some_and(|font| {
+        !covers(font, &locale.name) || !locale.strings.values().all(|text| covers(font, text))
+    });
+}
+
+pub struct LocalizationPlugin;
+impl Plugin for LocalizationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(RonAssetPlugin::<Locale>::new(&["locale"]))
+            .init_resource::<Localization>()
+            .add_system(update_localization);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_locale(language: Language) -> Locale {
+        let file = std::fs::read_to_string(format!("assets/{}", language.path())).unwrap();
+        ron::from_str(&file).unwrap()
+    }
+
+    #[test]
+    fn locales_have_every_english_key() {
+        let english = read_locale(Language::English);
+        for language in LANGUAGES {
+            let locale = read_locale(language);
+            let mut missing: Vec<&String> = english
+                .strings
+                .keys()
+                .filter(|key| !locale.strings.contains_key(*key))
+                .collect();
+            missing.sort();
+            assert!(
+                missing.is_empty(),
+                "{:?} is missing {:?}",
+                language,
+                missing
+            );
+        }
+    }
+
+    #[test]
+    fn arguments_are_filled_in() {
+        let mut localization = Localization {
+            language: Language::English,
+            name: String::new(),
+            strings: HashMap::new(),
+            fallback: HashMap::new(),
+            locales: HashMap::new(),
+            fonts: Default::default(),
+            fallback_fonts: Default::default(),
+            use_fallback_fonts: false,
+        };
+        localization
+            .fallback
+            .insert("win.hint".to_string(), "{key} to play {key}".to_string());
+        assert_eq!(
+            localization.format("win.hint", &[("key", &"X")]),
+            "X to play X"
+        );
+        assert_eq!(localization.get("missing"), "missing");
+        assert_eq!(Language::English.cycle(-1), Language::Russian);
+    }
+}
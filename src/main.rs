@@ -2,7 +2,7 @@
 
 use bevy::{
     prelude::*,
-    render::camera::ScalingMode,
+    render::camera::{RenderTarget, ScalingMode},
     // ecs::{
     //     schedule::ReportExecutionOrderAmbiguities,
     // },
@@ -15,29 +15,44 @@ use bevy::{
 };
 
 mod animation;
+mod camera;
 mod car;
 mod collisions;
 mod consts;
 mod coordinates;
+mod debug;
+mod hud;
 mod loader;
+mod localization;
 mod map;
+mod mixer;
+mod music;
 mod particles;
+mod pixel_perfect;
 mod player;
+mod positional_audio;
 mod rng_bag;
+mod settings;
+mod shapes;
+mod spatial_hash;
+mod stats;
+mod transitions;
+mod triggers;
 mod win_screen;
-use crate::consts::{AppState, SCALE, TILE_HEIGHT, TILE_SIZE, TILE_WIDTH};
+use crate::consts::{AppState, FIXED_TIMESTEP};
 
 fn main() {
+    let settings = settings::Settings::load();
+    let resolution = settings.video.resolution();
     let mut app = App::new();
     app.add_plugins(
         DefaultPlugins
             .set(WindowPlugin {
                 primary_window: Some(Window {
                     title: "Shoe Crosses the Road".to_string(),
-                    resolution: WindowResolution::new(
-                        TILE_WIDTH * SCALE * TILE_SIZE as f32,
-                        TILE_HEIGHT * SCALE * TILE_SIZE as f32,
-                    ),
+                    resolution: WindowResolution::new(resolution.x, resolution.y),
+                    mode: settings.video.window_mode(),
+                    present_mode: settings.video.present_mode(),
                     ..Default::default()
                 }),
                 ..default()
@@ -49,17 +64,31 @@ fn main() {
     // .add_plugin(bevy::diagnostic::EntityCountDiagnosticsPlugin::default())
     // Adds a system that prints diagnostics to the console
     // .add_plugin(LogDiagnosticsPlugin::default())
+    .insert_resource(settings)
+    .insert_resource(FixedTime::new_from_secs(FIXED_TIMESTEP))
     .add_system(close_on_esc)
     .add_state::<AppState>()
-    .add_system(animation::sprite_animation_system)
     .add_system(setup.in_schedule(OnEnter(AppState::Setup)))
+    .add_plugin(pixel_perfect::PixelPerfectPlugin)
     .add_plugin(loader::AssetsLoadingPlugin)
+    .add_plugin(animation::AnimationPlugin)
+    .add_plugin(camera::CameraEffectsPlugin)
     .add_plugin(coordinates::MovementPlugin)
     .add_plugin(collisions::CollisionPlugin)
+    .add_plugin(debug::CollisionDebugPlugin)
     .add_plugin(map::MapPlugin)
+    .add_plugin(settings::SettingsPlugin)
+    .add_plugin(localization::LocalizationPlugin)
+    .add_plugin(mixer::MixerPlugin)
+    .add_plugin(music::MusicPlugin)
+    .add_plugin(positional_audio::PositionalAudioPlugin)
     .add_plugin(car::CarPlugin)
     .add_plugin(player::PlayerPlugin)
-    .add_plugin(particles::ParticleSystem)
+    .add_plugin(triggers::TriggerPlugin)
+    .add_plugin(stats::StatsPlugin)
+    .add_plugin(hud::HudPlugin)
+    .add_plugin(transitions::TransitionPlugin)
+    .add_plugin(particles::ParticleSystem { pool_size: 512 })
     .add_plugin(win_screen::WinScreenPlugin)
     // .add_plugin(ConsoleDebugPlugin)
     .run();
@@ -67,13 +96,23 @@ fn main() {
     // println!("{}", schedule_graph_dot(&app.app.schedule));
 }
 
-fn setup(mut commands: Commands, mut state: ResMut<NextState<AppState>>) {
+fn setup(
+    mut commands: Commands,
+    mut state: ResMut<NextState<AppState>>,
+    screen: Res<pixel_perfect::GameScreen>,
+) {
+    // draws one game pixel per texel, pixel_perfect upscales it to the window
     let mut camera = Camera2dBundle::default();
+    camera.camera.target = RenderTarget::Image(screen.image.clone());
     camera.projection.viewport_origin = Vec2::new(0.0, 0.0);
     camera.projection.scaling_mode = ScalingMode::WindowSize(1.0);
-    camera.projection.scale = 1.0 / SCALE;
 
-    commands.spawn(camera);
+    commands.spawn((
+        camera,
+        camera::CameraShake::default(),
+        // ui goes on the window, not into the game screen
+        UiCameraConfig { show_ui: false },
+    ));
 
     state.set(AppState::AssetLoading);
 }
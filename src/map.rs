@@ -1,6 +1,9 @@
-use crate::collisions::Hitbox;
+use crate::collisions::{CollisionLayers, Hitbox};
 use crate::consts::{AppState, TILE_SIZE};
-use crate::coordinates::{Layer, TilePosition};
+use crate::coordinates::{KeepBetweenLevels, Layer, SpriteSize, TilePosition};
+use crate::particles::Emitter;
+use crate::transitions::screen_covered;
+use crate::triggers::{spawn_trigger_zone, TriggerData, TriggerKind, TriggerZone};
 use bevy::{prelude::*, reflect::TypeUuid};
 use bevy_common_assets::ron::RonAssetPlugin;
 use serde::{Deserialize, Serialize};
@@ -9,6 +12,23 @@ use serde::{Deserialize, Serialize};
 pub struct Levels {
     pub current_level: usize,
     pub levels: Vec<String>,
+    // load the current level again instead of moving on
+    pub retry: bool,
+    // replaying one level from the victory screen, which comes back after it
+    pub single_level: bool,
+}
+impl Levels {
+    // "levels/2_slow_cars.map" is "2_slow_cars", which is also its key in the locales
+    pub fn level_stem(&self, index: usize) -> &str {
+        let path = &self.levels[index];
+        let file_name = path.rsplit('/').next().unwrap_or(path);
+        file_name.strip_suffix(".map").unwrap_or(file_name)
+    }
+
+    // "levels/2_slow_cars.map" is called "2 slow cars"
+    pub fn level_name(&self, index: usize) -> String {
+        self.level_stem(index).replace('_', " ")
+    }
 }
 impl FromWorld for Levels {
     fn from_world(_: &mut World) -> Self {
@@ -23,6 +43,8 @@ impl FromWorld for Levels {
                 "levels/4_lanes_closed.map".to_string(),
                 "levels/too_busy.map".to_string(),
             ],
+            retry: false,
+            single_level: false,
         }
     }
 }
@@ -46,6 +68,18 @@ pub struct BusStop {
     pub tile_x: f32,
     pub tile_y: f32,
 }
+impl BusStop {
+    // the level ends on the row above the stop, where the player waits for the bus
+    pub fn goal_zone(&self) -> TriggerData {
+        TriggerData {
+            tile_x: self.tile_x,
+            tile_y: self.tile_y + 1.0,
+            width: 2.0,
+            height: 1.0,
+            kind: TriggerKind::Goal,
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone, Copy)]
 pub struct CarData {
@@ -67,6 +101,19 @@ pub struct Map {
     pub bus_stop: BusStop,
     pub cars: Vec<CarData>,
     pub walls: Vec<MapWallRow>,
+    // extra zones on top of the bus stop
+    #[serde(default)]
+    pub triggers: Vec<TriggerData>,
+    // path under assets, levels without one share the default track
+    #[serde(default)]
+    pub music: Option<String>,
+    // secs to beat for a medal on the victory screen
+    #[serde(default = "default_par_time")]
+    pub par_time: f32,
+}
+
+fn default_par_time() -> f32 {
+    15.0
 }
 
 #[derive(Resource)]
@@ -102,6 +149,9 @@ impl Default for CurrentLevel {
             },
             cars: vec![],
             walls: vec![],
+            triggers: vec![],
+            music: None,
+            par_time: default_par_time(),
         })
     }
 }
@@ -164,16 +214,13 @@ fn load_map_atlas(
     for wall_row in map.walls.iter() {
         for (c, wall_exists) in wall_row.columns.iter().enumerate() {
             if *wall_exists {
+                // order rows from top down
+                let wall_pos = TilePosition(Vec2::new(c as f32, (15 - wall_row.row) as f32));
                 commands
                     .spawn(SpriteSheetBundle {
                         texture_atlas: texture_atlas_handle.clone(),
                         transform: Transform {
-                            // order rows from top down
-                            translation: TilePosition(Vec2::new(
-                                c as f32,
-                                (15 - wall_row.row) as f32,
-                            ))
-                            .get_translation(
+                            translation: wall_pos.get_translation(
                                 Vec2::new(TILE_SIZE as f32, TILE_SIZE as f32),
                                 tile_layer + 0.1,
                             ),
@@ -183,7 +230,12 @@ fn load_map_atlas(
                         ..Default::default()
                     })
                     .insert(Layer(tile_layer + 0.1))
-                    .insert(Hitbox::new(Vec2::new(0.0, 0.0), Vec2::new(8.0, 8.0)))
+                    .insert(wall_pos.get_pixel_position())
+                    .insert(SpriteSize(Vec2::new(TILE_SIZE as f32, TILE_SIZE as f32)))
+                    .insert(
+                        Hitbox::new(Vec2::new(0.0, 0.0), Vec2::new(8.0, 8.0))
+                            .with_layers(CollisionLayers::WALL, CollisionLayers::ALL),
+                    )
                     .insert(Wall);
             }
         }
@@ -214,12 +266,28 @@ fn load_map_atlas(
             ..Default::default()
         })
         .insert(Layer(1.0));
+
+    spawn_trigger_zone(&mut commands, map.bus_stop.goal_zone());
+    for trigger in map.triggers.iter() {
+        spawn_trigger_zone(&mut commands, *trigger);
+    }
     state.set(AppState::InGame);
 }
 
 fn unload_level(
     mut commands: Commands,
-    sprite_query: Query<Entity, Or<(With<Sprite>, With<TextureAtlasSprite>)>>,
+    sprite_query: Query<
+        Entity,
+        (
+            Or<(
+                With<Sprite>,
+                With<TextureAtlasSprite>,
+                With<TriggerZone>,
+                With<Emitter>,
+            )>,
+            Without<KeepBetweenLevels>,
+        ),
+    >,
     mut levels: ResMut<Levels>,
     mut state: ResMut<NextState<AppState>>,
 ) {
@@ -227,7 +295,10 @@ fn unload_level(
         commands.entity(entity).despawn();
     }
 
-    if levels.current_level < levels.levels.len() - 1 {
+    if levels.retry {
+        levels.retry = false;
+        state.set(AppState::Loading);
+    } else if levels.current_level < levels.levels.len() - 1 && !levels.single_level {
         levels.current_level += 1;
         state.set(AppState::Loading);
     } else {
@@ -253,9 +324,15 @@ impl Plugin for MapPlugin {
             .add_plugin(RonAssetPlugin::<Map>::new(&["map"]))
             .insert_resource(CurrentLevel::default())
             .add_systems(
-                (load_current_map, load_map_atlas).chain()
+                (load_current_map, load_map_atlas)
+                    .chain()
                     .in_schedule(OnEnter(AppState::Loading)),
             )
-            .add_system(unload_level.in_schedule(OnEnter(AppState::LevelDone)));
+            // wait for the transition to cover the screen before tearing the level down
+            .add_system(
+                unload_level
+                    .run_if(screen_covered)
+                    .in_set(OnUpdate(AppState::LevelDone)),
+            );
     }
 }
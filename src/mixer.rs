@@ -0,0 +1,102 @@
+use crate::music::DuckMusic;
+use crate::settings::Settings;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AudioCategory {
+    Music,
+    Sfx,
+    Ui,
+}
+
+// every volume is 0 to 1 and gets multiplied by master
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+    pub ui: f32,
+    pub muted: bool,
+}
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            master: 0.8,
+            music: 0.6,
+            sfx: 1.0,
+            ui: 1.0,
+            muted: false,
+        }
+    }
+}
+impl AudioSettings {
+    pub fn volume(&self, category: AudioCategory) -> f32 {
+        if self.muted {
+            return 0.0;
+        }
+        let category_volume = match category {
+            AudioCategory::Music => self.music,
+            AudioCategory::Sfx => self.sfx,
+            AudioCategory::Ui => self.ui,
+        };
+        self.master * category_volume
+    }
+}
+
+// one shot sounds go through here instead of Audio so they get mixed
+pub struct PlaySound {
+    pub path: &'static str,
+    pub category: AudioCategory,
+}
+
+fn play_sounds(
+    mut events: EventReader<PlaySound>,
+    mut duck: EventWriter<DuckMusic>,
+    settings: Res<Settings>,
+    audio: Res<Audio>,
+    asset_server: Res<AssetServer>,
+) {
+    for ev in events.iter() {
+        let volume = settings.audio.volume(ev.category);
+        if volume <= 0.0 {
+            continue;
+        }
+        if ev.category == AudioCategory::Sfx {
+            duck.send(DuckMusic);
+        }
+        audio.play_with_settings(
+            asset_server.load(ev.path),
+            PlaybackSettings::ONCE.with_volume(volume),
+        );
+    }
+}
+
+pub struct MixerPlugin;
+impl Plugin for MixerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlaySound>().add_system(play_sounds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn category_volume() {
+        let mut settings = AudioSettings {
+            master: 0.5,
+            music: 0.5,
+            sfx: 1.0,
+            ui: 0.0,
+            muted: false,
+        };
+        assert_eq!(settings.volume(AudioCategory::Music), 0.25);
+        assert_eq!(settings.volume(AudioCategory::Sfx), 0.5);
+        assert_eq!(settings.volume(AudioCategory::Ui), 0.0);
+        settings.muted = true;
+        assert_eq!(settings.volume(AudioCategory::Sfx), 0.0);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::consts::AppState;
+use crate::map::CurrentLevel;
+use crate::mixer::AudioCategory;
+use crate::settings::Settings;
+use bevy::audio::AudioSink;
+use bevy::prelude::*;
+
+// used by levels that don't pick a track, the bundled levels all name theirs
+const LEVEL_TRACK: &str = "music/shoeroad_music_04.mp3";
+const MENU_TRACK: &str = "music/shoeroad_music_05.mp3";
+
+const MUSIC_VOLUME: f32 = 0.5;
+// secs to fade a track fully in or out
+const CROSSFADE_TIME: f32 = 1.5;
+// music drops to this fraction of its volume under loud sfx
+const DUCK_VOLUME: f32 = 0.4;
+const DUCK_TIME: f32 = 0.6;
+
+// sent by the mixer for every sfx it plays, the music gets out of the way
+pub struct DuckMusic;
+
+struct Track {
+    path: String,
+    sink: Handle<AudioSink>,
+    volume: f32,
+}
+
+#[derive(Resource, Default)]
+struct MusicManager {
+    current: Option<Track>,
+    fading_out: Vec<Track>,
+    duck_left: f32,
+}
+impl MusicManager {
+    // crossfades unless the track is already playing
+    fn play(
+        &mut self,
+        path: &str,
+        audio: &Audio,
+        asset_server: &AssetServer,
+        audio_sinks: &Assets<AudioSink>,
+    ) {
+        if self.current.as_ref().map(|track| track.path.as_str()) == Some(path) {
+            return;
+        }
+        // starts silent and fades in from update_music
+        let sink = audio.play_with_settings(
+            asset_server.load(path),
+            PlaybackSettings::LOOP.with_volume(0.0),
+        );
+        let track = Track {
+            path: path.to_string(),
+            sink: audio_sinks.get_handle(sink),
+            volume: 0.0,
+        };
+        if let Some(previous) = self.current.replace(track) {
+            self.fading_out.push(previous);
+        }
+    }
+}
+
+fn play_level_music(
+    mut music: ResMut<MusicManager>,
+    current_level: Res<CurrentLevel>,
+    audio: Res<Audio>,
+    asset_server: Res<AssetServer>,
+    audio_sinks: Res<Assets<AudioSink>>,
+) {
+    let path = current_level.0.music.as_deref().unwrap_or(LEVEL_TRACK);
+    music.play(path, &audio, &asset_server, &audio_sinks);
+}
+
+fn play_menu_music(
+    mut music: ResMut<MusicManager>,
+    audio: Res<Audio>,
+    asset_server: Res<AssetServer>,
+    audio_sinks: Res<Assets<AudioSink>>,
+) {
+    music.play(MENU_TRACK, &audio, &asset_server, &audio_sinks);
+}
+
+fn duck_music(mut events: EventReader<DuckMusic>, mut music: ResMut<MusicManager>) {
+    if events.iter().count() > 0 {
+        music.duck_left = DUCK_TIME;
+    }
+}
+
+// real time so music keeps fading through hit-stop
+fn update_music(
+    mut music: ResMut<MusicManager>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    // the mixer scales what's sent to the sinks, fades work on the unscaled volume
+    let mix = settings.audio.volume(AudioCategory::Music);
+    let delta = time.raw_delta_seconds();
+    let step = MUSIC_VOLUME * delta / CROSSFADE_TIME;
+    music.duck_left = (music.duck_left - delta).max(0.0);
+    let target = if music.duck_left > 0.0 {
+        MUSIC_VOLUME * DUCK_VOLUME
+    } else {
+        MUSIC_VOLUME
+    };
+
+    if let Some(track) = music.current.as_mut() {
+        // duck quickly, come back at crossfade speed
+        track.volume = if track.volume > target {
+            target
+        } else {
+            (track.volume + step).min(target)
+        };
+        if let Some(sink) = audio_sinks.get(&track.sink) {
+            sink.set_volume(track.volume * mix);
+        }
+    }
+
+    music.fading_out.retain_mut(|track| {
+        track.volume = (track.volume - step).max(0.0);
+        // kept until its sink shows up, or it would loop on at its starting volume
+        let Some(sink) = audio_sinks.get(&track.sink) else {
+            return true;
+        };
+        sink.set_volume(track.volume * mix);
+        if track.volume <= 0.0 {
+            sink.stop();
+            return false;
+        }
+        true
+    });
+}
+
+pub struct MusicPlugin;
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MusicManager>()
+            .add_event::<DuckMusic>()
+            .add_system(play_level_music.in_schedule(OnEnter(AppState::InGame)))
+            .add_system(play_menu_music.in_schedule(OnEnter(AppState::Finished)))
+            .add_system(duck_music.before(update_music))
+            .add_system(update_music);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map::Map;
+    use std::path::Path;
+
+    #[test]
+    fn level_tracks_exist() {
+        for entry in std::fs::read_dir("assets/levels").unwrap() {
+            let path = entry.unwrap().path();
+            let map: Map = ron::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            let track = map.music.unwrap_or_else(|| super::LEVEL_TRACK.to_string());
+            assert!(
+                Path::new("assets").join(&track).exists(),
+                "{:?} plays missing {}",
+                path,
+                track
+            );
+        }
+    }
+}
@@ -1,39 +1,141 @@
-use crate::animation::Animator;
+use crate::animation::{AnimationFrameEvent, Animator, FrameEvent};
 use crate::consts::AppState;
-use crate::coordinates::{Acceleration, Layer, PixelPosition, Velocity};
-use crate::player::Player;
+use crate::coordinates::{
+    Acceleration, KeepBetweenLevels, Layer, PixelPosition, PreviousPixelPosition, Velocity,
+};
+use bevy::asset::HandleId;
 use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy_common_assets::ron::RonAssetPlugin;
 use rand::Rng;
-use std::time::Duration;
+use serde::{Deserialize, Serialize};
 
-const BLOOD_COLOR: Color = Color::rgb(1.0, 0.0, 0.0);
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum EmitWhen {
+    // keeps spawning at `rate`
+    Always,
+    // spawns at `rate` while the owner plays this clip
+    WhileAnimation(String),
+    // spawns `burst` particles each time the owner's animation sends this event
+    OnFrameEvent(FrameEvent),
+}
 
-#[derive(Resource)]
-struct ParticleConfig {
-    x_velocity_range: std::ops::Range<f32>, // 1 to 2
-    y_velocity_range: std::ops::Range<f32>, // -3 to -1
-    lifetime: f32,
-    gravity: f32,
-}
-impl Default for ParticleConfig {
-    fn default() -> Self {
-        ParticleConfig {
-            x_velocity_range: -50.0..50.0,
-            y_velocity_range: -20.0..80.0,
-            lifetime: 16.0 / 60.0, // secs
-            gravity: -300.0,
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct ColorStop {
+    pub at: f32,
+    pub color: [f32; 4], // rgba
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct SizeStop {
+    pub at: f32,
+    pub size: f32,
+}
+
+// how an emitter's particles look and move, loaded from .emitter files
+#[derive(Serialize, Deserialize, TypeUuid, Clone)]
+#[uuid = "6c1a0d55-2f1e-4f7e-9a0b-3b8d1c7e4a92"]
+pub struct EmitterConfig {
+    pub emit: EmitWhen,
+    pub rate: f32, // particles per sec
+    pub burst: usize,
+    pub lifetime: f32, // secs
+    pub velocity_min: Vec2,
+    pub velocity_max: Vec2,
+    pub gravity: f32,
+    // stops are sorted by `at`, which runs from 0 at spawn to 1 at the end of the lifetime
+    pub colors: Vec<ColorStop>,
+    pub sizes: Vec<SizeStop>,
+    #[serde(default = "default_layer")]
+    pub layer: f32,
+}
+
+fn default_layer() -> f32 {
+    3.0
+}
+
+impl EmitterConfig {
+    pub fn color_at(&self, t: f32) -> Color {
+        let [r, g, b, a] = sample(
+            &self.colors,
+            t,
+            |stop| stop.at,
+            |stop| stop.color,
+            |from, to, t| [0, 1, 2, 3].map(|i| from[i] + (to[i] - from[i]) * t),
+        )
+        .unwrap_or([1.0, 1.0, 1.0, 1.0]);
+        Color::rgba(r, g, b, a)
+    }
+
+    pub fn size_at(&self, t: f32) -> f32 {
+        sample(
+            &self.sizes,
+            t,
+            |stop| stop.at,
+            |stop| stop.size,
+            |from, to, t| from + (to - from) * t,
+        )
+        .unwrap_or(1.0)
+    }
+}
+
+// linear interpolation between the two stops around t
+fn sample<S, T: Copy>(
+    stops: &[S],
+    t: f32,
+    at: impl Fn(&S) -> f32,
+    value: impl Fn(&S) -> T,
+    lerp: impl Fn(T, T, f32) -> T,
+) -> Option<T> {
+    let next = stops.iter().position(|stop| at(stop) > t);
+    match next {
+        None => stops.last().map(&value),
+        Some(0) => stops.first().map(&value),
+        Some(i) => {
+            let (from, to) = (&stops[i - 1], &stops[i]);
+            let span = at(to) - at(from);
+            Some(lerp(value(from), value(to), (t - at(from)) / span))
         }
     }
 }
 
+// spawns particles from its parent's PixelPosition, or its own when it has no parent,
+// give an entity child emitters to carry more than one
 #[derive(Component)]
-struct Particle;
-#[derive(Resource)]
-struct ParticleSpawnTimer(Timer);
+pub struct Emitter {
+    pub config: Handle<EmitterConfig>,
+    pub offset: Vec2,
+    // mirrors particle velocity, for things facing left
+    pub flip_x: bool,
+    // fractional particles carried over between frames
+    pending: f32,
+}
+impl Emitter {
+    pub fn new(config: Handle<EmitterConfig>) -> Self {
+        Emitter {
+            config,
+            offset: Vec2::ZERO,
+            flip_x: false,
+            pending: 0.0,
+        }
+    }
 
-#[derive(Component)]
-struct Lifetime {
-    current_lifetime: f32,
+    pub fn with_offset(mut self, offset: Vec2) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_flip_x(mut self, flip_x: bool) -> Self {
+        self.flip_x = flip_x;
+        self
+    }
+}
+
+// pooled particles are hidden and have no config while they wait to be reused
+#[derive(Component, Default)]
+struct Particle {
+    config: Option<Handle<EmitterConfig>>,
+    age: f32,
 }
 
 #[derive(Bundle)]
@@ -41,96 +143,305 @@ struct ParticleBundle {
     #[bundle]
     sprite_bundle: SpriteBundle,
     particle: Particle,
+    keep: KeepBetweenLevels,
     dust_pos: PixelPosition,
+    // particles move on the fixed step, so they're drawn interpolated like every other sprite
+    previous_pos: PreviousPixelPosition,
     layer: Layer,
-    lifetime: Lifetime,
     velocity: Velocity,
     acceleration: Acceleration,
 }
 
-// spawn dust to mitigate hitching
-fn setup_dust(mut commands: Commands) {
-    let dust_pos = PixelPosition(Vec2::new(200.0, 200.0));
-    commands.spawn(SpriteBundle {
-        sprite: Sprite {
-            color: BLOOD_COLOR,
-            custom_size: Some(Vec2::new(1.0, 1.0)),
-            ..Default::default()
-        },
-        transform: Transform {
-            translation: dust_pos.get_translation(Vec2::new(1.0, 1.0), 1.0),
-            ..Default::default()
-        },
-        ..Default::default()
-    });
-}
-
-fn spawn_new_dust(
+// every particle entity is spawned up front and recycled, so emitting never spawns or despawns
+#[derive(Resource, Default)]
+struct ParticlePool {
+    free: Vec<Entity>,
+}
+
+type PooledParticle<'a> = (
+    &'a mut Particle,
+    &'a mut Sprite,
+    &'a mut Visibility,
+    &'a mut PixelPosition,
+    &'a mut PreviousPixelPosition,
+    &'a mut Layer,
+    &'a mut Velocity,
+    &'a mut Acceleration,
+);
+
+fn fill_particle_pool(
     mut commands: Commands,
-    player_query: Query<(&PixelPosition, &Animator), With<Player>>,
-    config: Res<ParticleConfig>,
-    mut timer: ResMut<ParticleSpawnTimer>,
-    time: Res<Time>,
+    mut pool: ResMut<ParticlePool>,
+    capacity: Res<ParticlePoolCapacity>,
 ) {
-    if !timer.0.tick(time.delta()).finished() {
-        return;
-    }
-    let mut rng = rand::thread_rng();
-    let dust_layer = 3.0;
-    for (player_pos, animator) in player_query.iter() {
-        if animator.current_animation == 2 {
-            let dust_pos = PixelPosition(player_pos.0 + Vec2::new(8.0, 4.0));
-            commands.spawn(ParticleBundle {
-                sprite_bundle: SpriteBundle {
-                    sprite: Sprite {
-                        color: BLOOD_COLOR,
-                        custom_size: Some(Vec2::new(1.0, 1.0)),
+    pool.free = (0..capacity.0)
+        .map(|_| {
+            commands
+                .spawn(ParticleBundle {
+                    sprite_bundle: SpriteBundle {
+                        sprite: Sprite {
+                            custom_size: Some(Vec2::new(1.0, 1.0)),
+                            ..Default::default()
+                        },
+                        visibility: Visibility::Hidden,
                         ..Default::default()
                     },
-                    transform: Transform {
-                        translation: dust_pos.get_translation(Vec2::new(1.0, 1.0), 1.0),
-                        ..Default::default()
-                    },
-                    ..Default::default()
-                },
-                particle: Particle,
-                dust_pos,
-                layer: Layer(dust_layer),
-                lifetime: Lifetime {
-                    current_lifetime: config.lifetime,
-                },
-                velocity: Velocity(Vec2::new(
-                    rng.gen_range(config.x_velocity_range.clone()),
-                    rng.gen_range(config.y_velocity_range.clone()),
-                )),
-                acceleration: Acceleration(Vec2::new(0.0, config.gravity)),
-            });
-        }
+                    particle: Particle::default(),
+                    keep: KeepBetweenLevels,
+                    dust_pos: PixelPosition(Vec2::ZERO),
+                    previous_pos: PreviousPixelPosition(Vec2::ZERO),
+                    layer: Layer(0.0),
+                    velocity: Velocity(Vec2::ZERO),
+                    acceleration: Acceleration(Vec2::ZERO),
+                })
+                .id()
+        })
+        .collect();
+}
+
+fn spawn_particles(
+    pool: &mut ParticlePool,
+    particles: &mut Query<PooledParticle, With<Particle>>,
+    config_handle: &Handle<EmitterConfig>,
+    config: &EmitterConfig,
+    emitter: &Emitter,
+    position: Vec2,
+    count: usize,
+) {
+    let mut rng = rand::thread_rng();
+    let direction = Vec2::new(if emitter.flip_x { -1.0 } else { 1.0 }, 1.0);
+    for _ in 0..count {
+        // drop particles past the cap instead of growing the pool
+        let Some(entity) = pool.free.pop() else {
+            return;
+        };
+        let Ok((
+            mut particle,
+            mut sprite,
+            mut visibility,
+            mut pos,
+            mut previous,
+            mut layer,
+            mut v,
+            mut a,
+        )) = particles.get_mut(entity)
+        else {
+            continue;
+        };
+        // a config with min and max swapped shouldn't panic in gen_range
+        let min = config.velocity_min.min(config.velocity_max);
+        let max = config.velocity_min.max(config.velocity_max);
+        let velocity = Vec2::new(rng.gen_range(min.x..=max.x), rng.gen_range(min.y..=max.y));
+        *particle = Particle {
+            config: Some(config_handle.clone()),
+            age: 0.0,
+        };
+        sprite.color = config.color_at(0.0);
+        sprite.custom_size = Some(Vec2::splat(config.size_at(0.0)));
+        *visibility = Visibility::Inherited;
+        pos.0 = position + emitter.offset;
+        // a reused particle would otherwise be drawn sliding over from where it last died
+        previous.0 = pos.0;
+        layer.0 = config.layer;
+        v.0 = velocity * direction;
+        a.0 = Vec2::new(0.0, config.gravity);
     }
 }
 
-fn update_dust_lifetime(
-    mut commands: Commands,
-    mut query: Query<(Entity, &mut Lifetime)>,
+fn recycle_particle(
+    entity: Entity,
+    pool: &mut ParticlePool,
+    particle: &mut Particle,
+    visibility: &mut Visibility,
+    velocity: &mut Velocity,
+    acceleration: &mut Acceleration,
+) {
+    particle.config = None;
+    *visibility = Visibility::Hidden;
+    velocity.0 = Vec2::ZERO;
+    acceleration.0 = Vec2::ZERO;
+    pool.free.push(entity);
+}
+
+fn update_emitters(
+    mut pool: ResMut<ParticlePool>,
+    mut particles: Query<PooledParticle, With<Particle>>,
+    mut emitters: Query<(Entity, &mut Emitter, Option<&Parent>)>,
+    owners: Query<(&PixelPosition, Option<&Animator>), Without<Particle>>,
+    mut frame_events: EventReader<AnimationFrameEvent>,
+    configs: Res<Assets<EmitterConfig>>,
     time: Res<Time>,
 ) {
-    for (entity, mut lifetime) in query.iter_mut() {
-        lifetime.current_lifetime -= time.delta_seconds();
-        if lifetime.current_lifetime < 0.0 {
-            commands.entity(entity).despawn();
+    let frame_events: Vec<&AnimationFrameEvent> = frame_events.iter().collect();
+    for (entity, mut emitter, parent) in emitters.iter_mut() {
+        let owner = parent.map_or(entity, |parent| parent.get());
+        let Ok((owner_position, animator)) = owners.get(owner) else {
+            continue;
+        };
+        let Some(config) = configs.get(&emitter.config) else {
+            continue;
+        };
+        let position = owner_position.0;
+
+        let count = match &config.emit {
+            EmitWhen::Always => emitter.pending + config.rate * time.delta_seconds(),
+            EmitWhen::WhileAnimation(name)
+                if animator.map(|animator| &animator.current_animation) == Some(name) =>
+            {
+                emitter.pending + config.rate * time.delta_seconds()
+            }
+            EmitWhen::WhileAnimation(_) => 0.0,
+            EmitWhen::OnFrameEvent(event) => frame_events
+                .iter()
+                .filter(|ev| ev.entity == owner && ev.event == *event)
+                .map(|_| config.burst as f32)
+                .sum(),
+        };
+        emitter.pending = count.fract();
+
+        spawn_particles(
+            &mut pool,
+            &mut particles,
+            &emitter.config,
+            config,
+            &emitter,
+            position,
+            count as usize,
+        );
+    }
+}
+
+fn update_particles(
+    mut pool: ResMut<ParticlePool>,
+    mut query: Query<(
+        Entity,
+        &mut Particle,
+        &mut Sprite,
+        &mut Visibility,
+        &mut Velocity,
+        &mut Acceleration,
+    )>,
+    configs: Res<Assets<EmitterConfig>>,
+    time: Res<Time>,
+) {
+    // particles from the same emitter sit next to each other, so skip most asset lookups
+    let mut cached: Option<(HandleId, &EmitterConfig)> = None;
+    for (entity, mut particle, mut sprite, mut visibility, mut v, mut a) in query.iter_mut() {
+        let Some(handle) = &particle.config else {
+            continue;
+        };
+        let config = match cached {
+            Some((id, config)) if id == handle.id() => Some(config),
+            _ => configs.get(handle),
+        };
+        if let Some(config) = config {
+            cached = Some((handle.id(), config));
+        }
+        let Some(config) = config else {
+            recycle_particle(
+                entity,
+                &mut pool,
+                &mut particle,
+                &mut visibility,
+                &mut v,
+                &mut a,
+            );
+            continue;
+        };
+        particle.age += time.delta_seconds();
+        if particle.age > config.lifetime {
+            recycle_particle(
+                entity,
+                &mut pool,
+                &mut particle,
+                &mut visibility,
+                &mut v,
+                &mut a,
+            );
+            continue;
+        }
+        let t = particle.age / config.lifetime;
+        sprite.color = config.color_at(t);
+        sprite.custom_size = Some(Vec2::splat(config.size_at(t)));
+    }
+}
+
+// hide whatever is still flying when the level ends
+fn recycle_all_particles(
+    mut pool: ResMut<ParticlePool>,
+    mut query: Query<(
+        Entity,
+        &mut Particle,
+        &mut Visibility,
+        &mut Velocity,
+        &mut Acceleration,
+    )>,
+) {
+    for (entity, mut particle, mut visibility, mut v, mut a) in query.iter_mut() {
+        if particle.config.is_some() {
+            recycle_particle(
+                entity,
+                &mut pool,
+                &mut particle,
+                &mut visibility,
+                &mut v,
+                &mut a,
+            );
         }
     }
 }
 
-pub struct ParticleSystem;
+#[derive(Resource)]
+struct ParticlePoolCapacity(usize);
+
+pub struct ParticleSystem {
+    // most particles alive at once, emitters skip spawning when they're all in use
+    pub pool_size: usize,
+}
 impl Plugin for ParticleSystem {
     fn build(&self, app: &mut App) {
-        app.init_resource::<ParticleConfig>()
-            .insert_resource(ParticleSpawnTimer(Timer::new(
-                Duration::from_millis((0.75 / 60.0 * 1000.0) as u64),
-                TimerMode::Repeating,
-            )))
-            .add_system(setup_dust.in_schedule(OnEnter(AppState::InGame)))
-            .add_systems((spawn_new_dust, update_dust_lifetime).in_set(OnUpdate(AppState::InGame)));
+        app.add_plugin(RonAssetPlugin::<EmitterConfig>::new(&["emitter"]))
+            .insert_resource(ParticlePoolCapacity(self.pool_size))
+            .init_resource::<ParticlePool>()
+            .add_startup_system(fill_particle_pool)
+            .add_systems((update_emitters, update_particles).in_set(OnUpdate(AppState::InGame)))
+            .add_system(recycle_all_particles.in_schedule(OnExit(AppState::InGame)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_stops() {
+        let stops = [
+            SizeStop { at: 0.0, size: 1.0 },
+            SizeStop { at: 0.5, size: 3.0 },
+            SizeStop { at: 1.0, size: 2.0 },
+        ];
+        let size_at = |t| {
+            sample(
+                &stops,
+                t,
+                |stop| stop.at,
+                |stop| stop.size,
+                |a, b, t| a + (b - a) * t,
+            )
+        };
+        assert_eq!(size_at(0.0), Some(1.0));
+        assert_eq!(size_at(0.25), Some(2.0));
+        assert_eq!(size_at(0.75), Some(2.5));
+        assert_eq!(size_at(1.5), Some(2.0));
+        assert_eq!(
+            sample(
+                &[] as &[SizeStop],
+                0.5,
+                |stop| stop.at,
+                |stop| stop.size,
+                |a, _, _| a
+            ),
+            None
+        );
     }
 }
@@ -0,0 +1,195 @@
+use crate::consts::{SCALE, SCREEN_X_MAX, SCREEN_Y_MAX};
+use crate::settings::{Settings, Upscale};
+use bevy::core_pipeline::clear_color::ClearColorConfig;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::render_resource::{
+    AsBindGroup, Extent3d, ShaderRef, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureUsages,
+};
+use bevy::render::texture::ImageSampler;
+use bevy::render::view::RenderLayers;
+use bevy::sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle};
+use bevy::window::PrimaryWindow;
+
+// only the upscaled screen lives on this layer, the game is drawn on the default one
+const SCREEN_LAYER: u8 = 1;
+const LETTERBOX_COLOR: Color = Color::BLACK;
+
+// the game is drawn into this at native resolution, then upscaled to the window
+#[derive(Resource)]
+pub struct GameScreen {
+    pub image: Handle<Image>,
+    // logical window pixels per game pixel
+    pub scale: f32,
+}
+
+#[derive(AsBindGroup, TypeUuid, Clone)]
+#[uuid = "c32eb41b-db53-4492-8771-ab832292a78b"]
+struct SharpBilinearMaterial {
+    // physical window pixels per game pixel
+    #[uniform(0)]
+    pixels_per_texel: f32,
+    #[texture(1)]
+    #[sampler(2)]
+    texture: Handle<Image>,
+}
+impl Material2d for SharpBilinearMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/sharp_bilinear.wgsl".into()
+    }
+}
+
+#[derive(Component)]
+struct UpscaledScreen;
+
+fn game_screen_image() -> Image {
+    let size = Extent3d {
+        width: SCREEN_X_MAX as u32,
+        height: SCREEN_Y_MAX as u32,
+        ..Default::default()
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("game_screen"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST
+                | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        // the shader keeps it sharp, linear is only used to blend at texel edges
+        sampler_descriptor: ImageSampler::linear(),
+        ..Default::default()
+    };
+    image.resize(size);
+    image
+}
+
+fn setup_upscaled_screen(
+    mut commands: Commands,
+    screen: Res<GameScreen>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<SharpBilinearMaterial>>,
+) {
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                // after the game camera has drawn into the screen image
+                order: 1,
+                ..Default::default()
+            },
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::Custom(LETTERBOX_COLOR),
+            },
+            ..Default::default()
+        },
+        RenderLayers::layer(SCREEN_LAYER),
+    ));
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(shape::Quad::new(Vec2::ONE).into()).into(),
+            material: materials.add(SharpBilinearMaterial {
+                pixels_per_texel: SCALE,
+                texture: screen.image.clone(),
+            }),
+            ..Default::default()
+        },
+        RenderLayers::layer(SCREEN_LAYER),
+        UpscaledScreen,
+    ));
+}
+
+// the biggest scale that fits, in physical pixels so integer scaling stays exact on hidpi
+fn screen_scale(window_size: Vec2, upscale: Upscale) -> f32 {
+    let game_size = Vec2::new(SCREEN_X_MAX as f32, SCREEN_Y_MAX as f32);
+    let fit = (window_size / game_size).min_element();
+    match upscale {
+        Upscale::Integer => fit.floor().max(1.0),
+        Upscale::Fit => fit.max(1.0),
+    }
+}
+
+fn fit_screen_to_window(
+    mut screen: ResMut<GameScreen>,
+    settings: Res<Settings>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut quads: Query<(&mut Transform, &Handle<SharpBilinearMaterial>), With<UpscaledScreen>>,
+    mut materials: ResMut<Assets<SharpBilinearMaterial>>,
+    mut ui_scale: ResMut<UiScale>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let window_size = Vec2::new(
+        window.physical_width() as f32,
+        window.physical_height() as f32,
+    );
+    let scale_factor = window.scale_factor() as f32;
+    let pixels_per_texel = screen_scale(window_size, settings.video.upscale);
+    let size = Vec2::new(SCREEN_X_MAX as f32, SCREEN_Y_MAX as f32) * pixels_per_texel;
+    // letterbox edges on whole pixels, the window center can sit between two
+    let corner = ((window_size - size) / 2.0).floor();
+    let offset = corner + size / 2.0 - window_size / 2.0;
+
+    for (mut transform, material) in quads.iter_mut() {
+        transform.translation = (offset / scale_factor).extend(0.0);
+        transform.scale = (size / scale_factor).extend(1.0);
+        // only touch the material on change so its bind group isn't rebuilt every frame
+        let current = materials
+            .get(material)
+            .map(|material| material.pixels_per_texel);
+        if current.is_some() && current != Some(pixels_per_texel) {
+            if let Some(material) = materials.get_mut(material) {
+                material.pixels_per_texel = pixels_per_texel;
+            }
+        }
+    }
+
+    let scale = pixels_per_texel / scale_factor;
+    if screen.scale != scale {
+        screen.scale = scale;
+        // ui sizes are laid out for the default scale, and stay on whole steps under fit
+        // so text doesn't blur between pixels
+        let ui_pixels = pixels_per_texel.floor().max(1.0) / scale_factor;
+        ui_scale.scale = (ui_pixels / SCALE) as f64;
+    }
+}
+
+pub struct PixelPerfectPlugin;
+impl Plugin for PixelPerfectPlugin {
+    fn build(&self, app: &mut App) {
+        let image = app
+            .world
+            .resource_mut::<Assets<Image>>()
+            .add(game_screen_image());
+        app.add_plugin(Material2dPlugin::<SharpBilinearMaterial>::default())
+            .insert_resource(GameScreen { image, scale: 0.0 })
+            .add_startup_system(setup_upscaled_screen)
+            .add_system(fit_screen_to_window);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_scale_letterboxes() {
+        assert_eq!(
+            screen_scale(Vec2::new(1920.0, 1080.0), Upscale::Integer),
+            8.0
+        );
+        assert_eq!(
+            screen_scale(Vec2::new(1920.0, 1080.0), Upscale::Fit),
+            8.4375
+        );
+        // never smaller than one to one
+        assert_eq!(screen_scale(Vec2::new(100.0, 100.0), Upscale::Integer), 1.0);
+        assert_eq!(screen_scale(Vec2::new(100.0, 100.0), Upscale::Fit), 1.0);
+    }
+}
@@ -1,13 +1,27 @@
-use crate::animation::{Animation, AnimationFrame, Animations, Animator};
+use crate::animation::{
+    AnimationFrameEvent, AnimationIntent, AnimationSet, Animations, Animator, FrameEvent,
+};
+use crate::camera::Impact;
 use crate::car::Car;
-use crate::collisions::{CollisionEvent, Hurtbox};
+use crate::collisions::{
+    AddCollisionPair, CollisionEvent, CollisionLayers, CollisionPhase, Hurtbox,
+};
 use crate::consts::{AppState, SystemLabels, TILE_HEIGHT, TILE_WIDTH};
-use crate::coordinates::{Layer, PixelPosition, SpriteSize, TilePosition, Velocity};
+use crate::coordinates::{
+    Layer, PixelPosition, PreviousPixelPosition, SpriteSize, TilePosition, Velocity,
+};
 use crate::map::{load_current_map, CurrentLevel, Wall};
+use crate::mixer::{AudioCategory, PlaySound};
+use crate::particles::Emitter;
+use crate::settings::{settings_closed, Settings};
+use crate::transitions::no_transition;
+use crate::triggers::{player_enters_zone, TriggerEntered, TriggerKind};
 use bevy::prelude::*;
-use std::time::Duration;
 
 const PLAYER_SPEED: f32 = 60.0;
+const PLAYER_COLLIDES_WITH: CollisionLayers = CollisionLayers::CAR
+    .union(CollisionLayers::WALL)
+    .union(CollisionLayers::TRIGGER);
 
 #[derive(Component)]
 pub struct Player;
@@ -18,6 +32,14 @@ struct CurrentPosition(TilePosition);
 #[derive(Component)]
 struct NextPosition(Option<TilePosition>);
 
+// where the player rolls back to after getting hit
+#[derive(Component)]
+struct SpawnPoint(TilePosition);
+
+// walked into the goal, the level is done once the move onto its tile finishes
+#[derive(Component, Default)]
+struct HeadingForGoal(bool);
+
 #[derive(Bundle)]
 struct PlayerBundle {
     #[bundle]
@@ -25,7 +47,10 @@ struct PlayerBundle {
     player: Player,
     current_position: CurrentPosition,
     next_position: NextPosition,
+    spawn_point: SpawnPoint,
+    heading_for_goal: HeadingForGoal,
     pixel_position: PixelPosition,
+    previous_pixel_position: PreviousPixelPosition,
     layer: Layer,
     sprite_size: SpriteSize,
     hurtbox: Hurtbox,
@@ -38,128 +63,84 @@ fn setup_player(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    animation_sets: Res<Assets<AnimationSet>>,
     current_level: Res<CurrentLevel>,
 ) {
-    let texture_handle = asset_server.load("sprites/shoe_animation.png");
-    let sprite_size = SpriteSize(Vec2::new(8.0, 8.0));
-    let texture_atlas = TextureAtlas::from_grid(texture_handle, sprite_size.0, 4, 2, None, None);
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
+    let animation_set = animation_sets
+        .get(&asset_server.get_handle("animations/shoe.anim"))
+        .expect("shoe animations should be loaded before the level");
+    let sprite_size = SpriteSize(animation_set.tile_size);
+    let texture_atlas_handle = texture_atlases.add(animation_set.texture_atlas(&asset_server));
     let player_pos = TilePosition(Vec2::new(
         current_level.0.house.tile_x + 1.0,
         current_level.0.house.tile_y - 1.0,
     ));
     let player_layer = 2.0;
-    commands.spawn(PlayerBundle {
-        sprite_bundle: SpriteSheetBundle {
-            texture_atlas: texture_atlas_handle.clone(),
-            transform: Transform {
-                translation: player_pos.get_translation(Vec2::new(8.0, 8.0), player_layer),
+    commands
+        .spawn(PlayerBundle {
+            sprite_bundle: SpriteSheetBundle {
+                texture_atlas: texture_atlas_handle.clone(),
+                transform: Transform {
+                    translation: player_pos.get_translation(Vec2::new(8.0, 8.0), player_layer),
+                    ..Default::default()
+                },
                 ..Default::default()
             },
-            ..Default::default()
-        },
-        player: Player,
-        current_position: CurrentPosition(player_pos),
-        next_position: NextPosition(None),
-        pixel_position: PixelPosition(player_pos.get_pixel_position().0),
-        velocity: Velocity(Vec2::new(0.0, 0.0)),
-        layer: Layer(player_layer),
-        sprite_size,
-        hurtbox: Hurtbox::new(Vec2::new(-0.5, 0.0), Vec2::new(7.0, 8.0)),
-        animator: Animator::default(),
-        animations: Animations {
-            animations: vec![
-                // idle animation
-                Animation {
-                    frames: vec![
-                        AnimationFrame {
-                            atlas_handle: texture_atlas_handle.clone(),
-                            atlas_index: 0,
-                            duration: Duration::from_secs_f32(3.0 - 1.0 / 6.0),
-                        },
-                        AnimationFrame {
-                            atlas_handle: texture_atlas_handle.clone(),
-                            atlas_index: 3,
-                            duration: Duration::from_secs_f32(1.0 / 6.0),
-                        },
-                    ],
-                },
-                // walk animation
-                Animation {
-                    frames: vec![
-                        AnimationFrame {
-                            atlas_handle: texture_atlas_handle.clone(),
-                            atlas_index: 1,
-                            duration: Duration::from_secs_f32(1.0 / 15.0),
-                        },
-                        AnimationFrame {
-                            atlas_handle: texture_atlas_handle.clone(),
-                            atlas_index: 2,
-                            duration: Duration::from_secs_f32(1.0 / 15.0),
-                        },
-                    ],
-                },
-                // rolling animation
-                Animation {
-                    frames: vec![
-                        AnimationFrame {
-                            atlas_handle: texture_atlas_handle.clone(),
-                            atlas_index: 4,
-                            duration: Duration::from_secs_f32(1.0 / 15.0),
-                        },
-                        AnimationFrame {
-                            atlas_handle: texture_atlas_handle.clone(),
-                            atlas_index: 5,
-                            duration: Duration::from_secs_f32(1.0 / 15.0),
-                        },
-                        AnimationFrame {
-                            atlas_handle: texture_atlas_handle.clone(),
-                            atlas_index: 6,
-                            duration: Duration::from_secs_f32(1.0 / 15.0),
-                        },
-                        AnimationFrame {
-                            atlas_handle: texture_atlas_handle,
-                            atlas_index: 7,
-                            duration: Duration::from_secs_f32(1.0 / 15.0),
-                        },
-                    ],
-                },
-            ],
-        },
-    });
+            player: Player,
+            current_position: CurrentPosition(player_pos),
+            next_position: NextPosition(None),
+            spawn_point: SpawnPoint(player_pos),
+            heading_for_goal: HeadingForGoal::default(),
+            pixel_position: player_pos.get_pixel_position(),
+            previous_pixel_position: PreviousPixelPosition(player_pos.get_pixel_position().0),
+            velocity: Velocity(Vec2::new(0.0, 0.0)),
+            layer: Layer(player_layer),
+            sprite_size,
+            hurtbox: Hurtbox::new(Vec2::new(-0.5, 0.0), Vec2::new(7.0, 8.0))
+                .with_layers(CollisionLayers::PLAYER, PLAYER_COLLIDES_WITH),
+            animator: Animator::new("idle"),
+            animations: Animations::from_set(animation_set, texture_atlas_handle),
+        })
+        .with_children(|parent| {
+            // children of a sprite need a transform and visibility for the hierarchy to propagate
+            parent.spawn((
+                Emitter::new(asset_server.load("particles/blood.emitter"))
+                    .with_offset(Vec2::new(8.0, 4.0)),
+                SpatialBundle::default(),
+            ));
+            parent.spawn((
+                Emitter::new(asset_server.load("particles/dust.emitter"))
+                    .with_offset(Vec2::new(4.0, 0.0)),
+                SpatialBundle::default(),
+            ));
+        });
 }
 
 fn player_input(
     mut commands: Commands,
     keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
     touches: Res<Touches>,
-    mut player_query: Query<
-        (
-            Entity,
-            &CurrentPosition,
-            &mut Animator,
-            &Layer,
-            &NextPosition,
-        ),
-        With<Player>,
-    >,
+    mut player_query: Query<(Entity, &CurrentPosition, &Layer, &NextPosition), With<Player>>,
+    mut intents: EventWriter<AnimationIntent>,
 ) {
-    for (player, current_position, mut animator, layer, next_position) in player_query.iter_mut() {
-        if next_position.0 != None {
+    let controls = &settings.controls;
+    for (player, current_position, layer, next_position) in player_query.iter_mut() {
+        if next_position.0.is_some() {
             continue;
         }
 
         let mut next_position = TilePosition(current_position.0 .0);
-        if keyboard_input.pressed(KeyCode::Left) {
+        if keyboard_input.pressed(controls.left) {
             next_position.0.x -= 1.0;
         }
-        if keyboard_input.pressed(KeyCode::Right) {
+        if keyboard_input.pressed(controls.right) {
             next_position.0.x += 1.0;
         }
-        if keyboard_input.pressed(KeyCode::Up) {
+        if keyboard_input.pressed(controls.up) {
             next_position.0.y += 1.0;
         }
-        if keyboard_input.pressed(KeyCode::Down) {
+        if keyboard_input.pressed(controls.down) {
             next_position.0.y -= 1.0;
         }
 
@@ -188,17 +169,19 @@ fn player_input(
             return;
         }
 
-        // limit player to screen bounds
+        // limit player to screen bounds, the top and bottom rows are under the hud
         if next_position.0.x < 0.0
             || next_position.0.x > TILE_WIDTH - 1.0
-            || next_position.0.y < 0.0
-            || next_position.0.y > TILE_HEIGHT - 1.0
+            || next_position.0.y < 1.0
+            || next_position.0.y > TILE_HEIGHT - 2.0
         {
             return;
         }
 
-        animator.current_animation = 1;
-        animator.current_frame = 0;
+        intents.send(AnimationIntent {
+            entity: player,
+            intent: "move",
+        });
         commands
             .entity(player)
             .insert(NextPosition(Some(next_position)));
@@ -219,18 +202,23 @@ fn player_movement_done(
         (
             Entity,
             &NextPosition,
-            &Transform,
+            &PixelPosition,
             &Velocity,
-            &mut Animator,
+            &mut Hurtbox,
             &Layer,
+            &HeadingForGoal,
         ),
         With<Player>,
     >,
+    mut intents: EventWriter<AnimationIntent>,
+    mut state: ResMut<NextState<AppState>>,
 ) {
-    for (player, next_position, transform, v, mut animator, layer) in player_query.iter_mut() {
+    for (player, next_position, pixel_position, v, mut hurtbox, layer, heading_for_goal) in
+        player_query.iter_mut()
+    {
         if let Some(next_position) = next_position.0 {
-            let diff =
-                next_position.get_translation(Vec2::new(8.0, 8.0), layer.0) - transform.translation;
+            let diff = next_position.get_translation(Vec2::new(8.0, 8.0), layer.0)
+                - pixel_position.get_translation(Vec2::new(8.0, 8.0), layer.0);
             if diff.truncate().dot(v.0) <= 0.0 {
                 let new_current_position = CurrentPosition(next_position);
                 let new_pixel_position = new_current_position.0.get_pixel_position();
@@ -240,120 +228,167 @@ fn player_movement_done(
                     .insert(new_pixel_position)
                     .insert(Velocity(Vec2::new(0.0, 0.0)))
                     .insert(NextPosition(None));
-                animator.current_animation = 0;
-                animator.current_frame = 0;
+                hurtbox.mask = PLAYER_COLLIDES_WITH;
+                intents.send(AnimationIntent {
+                    entity: player,
+                    intent: "stop",
+                });
+                if heading_for_goal.0 {
+                    state.set(AppState::LevelDone);
+                }
             }
         }
     }
 }
 
 fn player_step_sfx(
-    player_query: Query<&Animator, With<Player>>,
-    mut last_frame: Local<usize>,
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
+    mut frame_events: EventReader<AnimationFrameEvent>,
+    player_query: Query<(), With<Player>>,
+    mut sounds: EventWriter<PlaySound>,
 ) {
-    let animator = player_query.single();
-    if animator.current_animation == 1 && *last_frame != animator.current_frame {
-        let sfx = asset_server.load("sfx/step.ogg");
-        audio.play(sfx);
+    for ev in frame_events.iter() {
+        if ev.event == FrameEvent::Footstep && player_query.contains(ev.entity) {
+            sounds.send(PlaySound {
+                path: "sfx/step.ogg",
+                category: AudioCategory::Sfx,
+            });
+        }
     }
-    *last_frame = animator.current_frame;
 }
 
 const PLAYER_ROLLING_SPEED: f32 = 60.0;
 fn player_collides_car(
     mut commands: Commands,
     mut event_reader: EventReader<CollisionEvent<Player, Car>>,
-    mut player_query: Query<(Entity, &mut Animator, &PixelPosition, &Layer), With<Player>>,
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
-    level: Res<CurrentLevel>,
+    mut player_query: Query<
+        (
+            Entity,
+            &mut Hurtbox,
+            &PixelPosition,
+            &SpawnPoint,
+            &Layer,
+            &mut HeadingForGoal,
+        ),
+        With<Player>,
+    >,
+    mut intents: EventWriter<AnimationIntent>,
+    mut impacts: EventWriter<Impact>,
 ) {
-    if event_reader.iter().next().is_some() {
-        let (player, mut animator, current_position, layer) = player_query.single_mut();
+    let Some(hit) = event_reader
+        .iter()
+        .filter(|ev| ev.phase == CollisionPhase::Started)
+        .last()
+    else {
+        return;
+    };
+    let car = hit.collided_with;
 
-        if animator.current_animation == 2 {
-            return;
-        }
-        let spawn_pos = TilePosition(Vec2::new(
-            level.0.house.tile_x + 1.0,
-            level.0.house.tile_y - 1.0,
-        ));
-        commands
-            .entity(player)
-            .insert(NextPosition(Some(spawn_pos)));
+    let (player, mut hurtbox, current_position, spawn_point, layer, mut heading_for_goal) =
+        player_query.single_mut();
+    // rolling home doesn't finish the level
+    heading_for_goal.0 = false;
+    let spawn_pos = spawn_point.0;
+    commands
+        .entity(player)
+        .insert(NextPosition(Some(spawn_pos)));
+    // can't get hit again or stopped by walls while rolling home.
+    // an empty mask means the collision system skips the player entirely,
+    // so no events pile up the way they did when handlers filtered on a marker
+    hurtbox.mask = CollisionLayers::NONE;
 
-        let current_translation = current_position.get_translation(Vec2::new(8.0, 8.0), layer.0);
-        let next_translation = spawn_pos.get_translation(Vec2::new(8.0, 8.0), layer.0);
-        let direction = (next_translation - current_translation).normalize();
-        commands
-            .entity(player)
-            .insert(Velocity(direction.truncate() * PLAYER_ROLLING_SPEED));
-        animator.current_animation = 2;
-        animator.current_frame = 0;
-        let sfx = asset_server.load("sfx/honk.ogg");
-        audio.play(sfx);
-    }
+    let current_translation = current_position.get_translation(Vec2::new(8.0, 8.0), layer.0);
+    let next_translation = spawn_pos.get_translation(Vec2::new(8.0, 8.0), layer.0);
+    let direction = (next_translation - current_translation).normalize();
+    commands
+        .entity(player)
+        .insert(Velocity(direction.truncate() * PLAYER_ROLLING_SPEED));
+    intents.send(AnimationIntent {
+        entity: player,
+        intent: "hit",
+    });
+    impacts.send(Impact {
+        trauma: 0.6,
+        hit_stop: 0.08,
+    });
+    // the car that hit plays its honk clip, car_honk_sfx plays the sound
+    intents.send(AnimationIntent {
+        entity: car,
+        intent: "honk",
+    });
 }
 
 fn player_collides_wall(
     mut commands: Commands,
     mut event_reader: EventReader<CollisionEvent<Player, Wall>>,
-    mut player_query: Query<(Entity, &mut Animator, &CurrentPosition), With<Player>>,
+    mut player_query: Query<(Entity, &CurrentPosition, &mut HeadingForGoal), With<Player>>,
+    mut intents: EventWriter<AnimationIntent>,
 ) {
-    if event_reader.iter().next().is_some() {
-        let (player, mut animator, current_position) = player_query.single_mut();
-
-        if animator.current_animation == 2 {
-            return;
-        }
-        commands
-            .entity(player)
-            .insert(current_position.0.get_pixel_position())
-            .insert(Velocity(Vec2::new(0.0, 0.0)))
-            .insert(NextPosition(None));
-        animator.current_animation = 0;
-        animator.current_frame = 0;
+    // read them all so none are left over to stop the player again next step
+    let events: Vec<_> = event_reader.iter().collect();
+    if !events.iter().any(|ev| ev.phase == CollisionPhase::Started) {
+        return;
     }
+
+    let (player, current_position, mut heading_for_goal) = player_query.single_mut();
+    heading_for_goal.0 = false;
+    commands
+        .entity(player)
+        .insert(current_position.0.get_pixel_position())
+        .insert(Velocity(Vec2::new(0.0, 0.0)))
+        .insert(NextPosition(None));
+    intents.send(AnimationIntent {
+        entity: player,
+        intent: "stop",
+    });
 }
 
-fn level_complete(
-    mut state: ResMut<NextState<AppState>>,
-    player_query: Query<&CurrentPosition, With<Player>>,
-    level: Res<CurrentLevel>,
+// zones are entered partway through a move, the goal waits for player_movement_done
+fn player_enters_trigger(
+    mut event_reader: EventReader<TriggerEntered>,
+    mut player_query: Query<(&mut SpawnPoint, &mut HeadingForGoal), With<Player>>,
 ) {
-    let current_position = player_query.single();
-
-    if (current_position.0 .0.y - level.0.bus_stop.tile_y - 1.0).abs() < 0.1 {
-        state.set(AppState::LevelDone);
+    for ev in event_reader.iter() {
+        let Ok((mut spawn_point, mut heading_for_goal)) = player_query.get_mut(ev.player) else {
+            continue;
+        };
+        match ev.kind {
+            TriggerKind::Goal => heading_for_goal.0 = true,
+            TriggerKind::Checkpoint => spawn_point.0 = ev.tile_position,
+        }
     }
 }
 
 pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(
-            setup_player
-                .in_schedule(OnEnter(AppState::Loading))
-                .after(load_current_map),
-        )
-        .add_systems(
-            (
-                player_input.before(SystemLabels::PlayerMovement),
-                player_movement_done.in_set(SystemLabels::PlayerMovement),
+        app.add_collision_pair::<Player, Car>()
+            .add_collision_pair::<Player, Wall>()
+            .add_system(
+                setup_player
+                    .in_schedule(OnEnter(AppState::Loading))
+                    .after(load_current_map),
             )
-                .in_set(OnUpdate(AppState::InGame)),
-        )
-        .add_systems(
-            (
-                player_step_sfx,
-                level_complete,
-                player_collides_car,
-                player_collides_wall,
+            .add_systems(
+                (
+                    player_input.run_if(no_transition).run_if(settings_closed),
+                    player_step_sfx,
+                )
+                    .in_set(OnUpdate(AppState::InGame)),
             )
-                .in_set(OnUpdate(AppState::InGame))
-                .after(SystemLabels::PlayerMovement),
-        );
+            .add_systems(
+                (
+                    player_movement_done.in_set(SystemLabels::PlayerMovement),
+                    player_enters_trigger
+                        .after(player_enters_zone)
+                        .before(SystemLabels::PlayerMovement),
+                    player_collides_car.after(SystemLabels::PlayerMovement),
+                    // a car hit in the same step wins over getting stopped by a wall
+                    player_collides_wall
+                        .after(SystemLabels::PlayerMovement)
+                        .before(player_collides_car),
+                )
+                    .distributive_run_if(in_state(AppState::InGame))
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
     }
 }
@@ -0,0 +1,158 @@
+use crate::car::Car;
+use crate::consts::{SCREEN_X_MAX, SCREEN_Y_MAX, TILE_SIZE};
+use crate::coordinates::{PixelPosition, SpriteSize};
+use crate::mixer::AudioCategory;
+use crate::music::DuckMusic;
+use crate::player::Player;
+use crate::settings::Settings;
+use bevy::audio::SpatialAudioSink;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+const ENGINE_SOUND: &str = "sfx/engine.wav";
+const ENGINE_VOLUME: f32 = 0.25;
+// pixels from the listener to hard left or right
+const HEARING_WIDTH: f32 = 64.0;
+const EAR_GAP: f32 = 0.1;
+// how much quieter each lane away gets
+const LANE_FALLOFF: f32 = 0.5;
+
+// like PlaySound, but panned and attenuated from where it happens
+pub struct PlaySoundAt {
+    pub path: &'static str,
+    pub category: AudioCategory,
+    // center of whatever made the sound, in game pixels
+    pub position: Vec2,
+}
+
+// looping engine sounds by car, stopped once the car is gone
+#[derive(Resource, Default)]
+struct EngineSounds(HashMap<Entity, Handle<SpatialAudioSink>>);
+
+fn center(position: &PixelPosition, size: &SpriteSize) -> Vec2 {
+    position.0 + size.0 / 2.0
+}
+
+// everything is heard from the player, or the middle of the screen without one
+fn listener(player_query: &Query<(&PixelPosition, &SpriteSize), With<Player>>) -> Vec2 {
+    player_query.get_single().map_or(
+        Vec2::new(SCREEN_X_MAX as f32, SCREEN_Y_MAX as f32) / 2.0,
+        |(position, size)| center(position, size),
+    )
+}
+
+// the listener sits at the origin facing the emitter, so only x moves the sound around.
+// rodio 0.17 turns up the ear further from the emitter, so x is mirrored to pan the right way
+fn emitter_position(listener: Vec2, source: Vec2) -> Vec3 {
+    Vec3::new((listener.x - source.x) / HEARING_WIDTH, 0.0, 1.0)
+}
+
+fn lane_volume(listener: Vec2, source: Vec2) -> f32 {
+    let lanes = ((source.y - listener.y).abs() / TILE_SIZE as f32).round();
+    1.0 / (1.0 + LANE_FALLOFF * lanes)
+}
+
+fn play_sounds_at(
+    mut events: EventReader<PlaySoundAt>,
+    mut duck: EventWriter<DuckMusic>,
+    player_query: Query<(&PixelPosition, &SpriteSize), With<Player>>,
+    settings: Res<Settings>,
+    audio: Res<Audio>,
+    asset_server: Res<AssetServer>,
+) {
+    let listener = listener(&player_query);
+    for ev in events.iter() {
+        let volume = settings.audio.volume(ev.category) * lane_volume(listener, ev.position);
+        if volume <= 0.0 {
+            continue;
+        }
+        if ev.category == AudioCategory::Sfx {
+            duck.send(DuckMusic);
+        }
+        audio.play_spatial_with_settings(
+            asset_server.load(ev.path),
+            PlaybackSettings::ONCE.with_volume(volume),
+            Transform::IDENTITY,
+            EAR_GAP,
+            emitter_position(listener, ev.position),
+        );
+    }
+}
+
+fn start_engine_sounds(
+    mut engines: ResMut<EngineSounds>,
+    car_query: Query<Entity, Added<Car>>,
+    audio: Res<Audio>,
+    asset_server: Res<AssetServer>,
+    audio_sinks: Res<Assets<SpatialAudioSink>>,
+) {
+    for car in car_query.iter() {
+        // starts silent, update_engine_sounds sets the volume once the sink exists
+        let sink = audio.play_spatial_with_settings(
+            asset_server.load(ENGINE_SOUND),
+            PlaybackSettings::LOOP.with_volume(0.0),
+            Transform::IDENTITY,
+            EAR_GAP,
+            Vec3::Z,
+        );
+        engines.0.insert(car, audio_sinks.get_handle(sink));
+    }
+}
+
+fn update_engine_sounds(
+    mut engines: ResMut<EngineSounds>,
+    car_query: Query<(&PixelPosition, &SpriteSize), With<Car>>,
+    player_query: Query<(&PixelPosition, &SpriteSize), With<Player>>,
+    settings: Res<Settings>,
+    audio_sinks: Res<Assets<SpatialAudioSink>>,
+    time: Res<Time>,
+) {
+    let listener = listener(&player_query);
+    let volume = settings.audio.volume(AudioCategory::Sfx) * ENGINE_VOLUME;
+    // the settings menu stops time and hit-stop pauses it, cars aren't driving either way
+    let paused = time.is_paused() || time.relative_speed() == 0.0;
+    engines.0.retain(|car, sink| {
+        let Some(sink) = audio_sinks.get(sink) else {
+            return true;
+        };
+        let Ok((position, size)) = car_query.get(*car) else {
+            sink.stop();
+            return false;
+        };
+        if paused {
+            sink.pause();
+        } else if sink.is_paused() {
+            sink.play();
+        }
+        let source = center(position, size);
+        sink.set_emitter_position(emitter_position(listener, source));
+        sink.set_volume(volume * lane_volume(listener, source));
+        true
+    });
+}
+
+pub struct PositionalAudioPlugin;
+impl Plugin for PositionalAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EngineSounds>()
+            .add_event::<PlaySoundAt>()
+            .add_system(play_sounds_at)
+            .add_system(start_engine_sounds)
+            .add_system(update_engine_sounds.after(start_engine_sounds));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lanes_attenuate_and_x_pans() {
+        let listener = Vec2::new(64.0, 64.0);
+        assert_eq!(lane_volume(listener, Vec2::new(0.0, 64.0)), 1.0);
+        assert_eq!(lane_volume(listener, Vec2::new(64.0, 80.0)), 0.5);
+        // mirrored for rodio, see emitter_position
+        assert!(emitter_position(listener, Vec2::new(128.0, 64.0)).x < 0.0);
+        assert_eq!(emitter_position(listener, listener).x, 0.0);
+    }
+}
@@ -0,0 +1,528 @@
+use crate::camera::CameraEffectsSettings;
+use crate::consts::{SCALE, TILE_HEIGHT, TILE_SIZE, TILE_WIDTH};
+use crate::localization::{Language, Localization};
+use crate::mixer::{AudioCategory, AudioSettings, PlaySound};
+use bevy::prelude::*;
+use bevy::window::{PresentMode, PrimaryWindow, WindowMode};
+use serde::{Deserialize, Serialize};
+
+// next to the executable's working directory, not in assets since it's written at runtime
+const SETTINGS_PATH: &str = "settings.ron";
+const MAX_SCALE: u32 = 8;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Upscale {
+    // whole window pixels per game pixel, letterboxed
+    #[default]
+    Integer,
+    // fills as much of the window as it can, with sharp bilinear filtering
+    Fit,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct VideoSettings {
+    // window pixels per game pixel when windowed, the window can still be resized
+    pub scale: u32,
+    pub upscale: Upscale,
+    pub fullscreen: bool,
+    pub vsync: bool,
+}
+impl Default for VideoSettings {
+    fn default() -> Self {
+        VideoSettings {
+            scale: SCALE as u32,
+            upscale: Upscale::Integer,
+            fullscreen: false,
+            vsync: true,
+        }
+    }
+}
+impl VideoSettings {
+    pub fn resolution(&self) -> Vec2 {
+        Vec2::new(TILE_WIDTH, TILE_HEIGHT) * TILE_SIZE as f32 * self.scale as f32
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        if self.fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        }
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Controls {
+    pub up: KeyCode,
+    pub down: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub restart: KeyCode,
+}
+impl Default for Controls {
+    fn default() -> Self {
+        Controls {
+            up: KeyCode::Up,
+            down: KeyCode::Down,
+            left: KeyCode::Left,
+            right: KeyCode::Right,
+            restart: KeyCode::X,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Resource, Clone, PartialEq, Default, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub video: VideoSettings,
+    pub audio: AudioSettings,
+    pub controls: Controls,
+    pub camera_effects: CameraEffectsSettings,
+    pub language: Language,
+}
+impl Settings {
+    // missing or broken files fall back to the defaults
+    pub fn load() -> Self {
+        let Ok(file) = std::fs::read_to_string(SETTINGS_PATH) else {
+            return Settings::default();
+        };
+        ron::from_str(&file).unwrap_or_else(|e| {
+            warn!("couldn't parse {}: {}", SETTINGS_PATH, e);
+            Settings::default()
+        })
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .map_err(|e| e.to_string())
+            .and_then(|file| std::fs::write(SETTINGS_PATH, file).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            warn!("couldn't save {}: {}", SETTINGS_PATH, e);
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum SettingRow {
+    Language,
+    Scale,
+    Upscale,
+    Fullscreen,
+    Vsync,
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    UiVolume,
+    Mute,
+    ScreenShake,
+    HitStop,
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Restart,
+}
+const ROWS: [SettingRow; 17] = [
+    SettingRow::Language,
+    SettingRow::Scale,
+    SettingRow::Upscale,
+    SettingRow::Fullscreen,
+    SettingRow::Vsync,
+    SettingRow::MasterVolume,
+    SettingRow::MusicVolume,
+    SettingRow::SfxVolume,
+    SettingRow::UiVolume,
+    SettingRow::Mute,
+    SettingRow::ScreenShake,
+    SettingRow::HitStop,
+    SettingRow::MoveUp,
+    SettingRow::MoveDown,
+    SettingRow::MoveLeft,
+    SettingRow::MoveRight,
+    SettingRow::Restart,
+];
+
+fn on_off_key(value: bool) -> &'static str {
+    if value {
+        "settings.on"
+    } else {
+        "settings.off"
+    }
+}
+
+impl SettingRow {
+    fn key(&self) -> &'static str {
+        match self {
+            SettingRow::Language => "settings.language",
+            SettingRow::Scale => "settings.window",
+            SettingRow::Upscale => "settings.upscale",
+            SettingRow::Fullscreen => "settings.fullscreen",
+            SettingRow::Vsync => "settings.vsync",
+            SettingRow::MasterVolume => "settings.master",
+            SettingRow::MusicVolume => "settings.music",
+            SettingRow::SfxVolume => "settings.sfx",
+            SettingRow::UiVolume => "settings.ui",
+            SettingRow::Mute => "settings.mute",
+            SettingRow::ScreenShake => "settings.screen_shake",
+            SettingRow::HitStop => "settings.hit_stop",
+            SettingRow::MoveUp => "settings.move_up",
+            SettingRow::MoveDown => "settings.move_down",
+            SettingRow::MoveLeft => "settings.move_left",
+            SettingRow::MoveRight => "settings.move_right",
+            SettingRow::Restart => "settings.restart",
+        }
+    }
+
+    fn label(&self, settings: &Settings, localization: &Localization) -> String {
+        let percent = |volume: f32| format!("{:>3}%", (volume * 100.0).round());
+        let on_off = |value: bool| localization.get(on_off_key(value)).to_string();
+        let video = &settings.video;
+        let audio = &settings.audio;
+        let effects = &settings.camera_effects;
+        let controls = &settings.controls;
+        let value = match self {
+            SettingRow::Language => localization.language_name().to_string(),
+            SettingRow::Scale => format!("x{}", video.scale),
+            SettingRow::Upscale => match video.upscale {
+                Upscale::Integer => localization.get("settings.upscale.integer").to_string(),
+                Upscale::Fit => localization.get("settings.upscale.fit").to_string(),
+            },
+            SettingRow::Fullscreen => on_off(video.fullscreen),
+            SettingRow::Vsync => on_off(video.vsync),
+            SettingRow::MasterVolume => percent(audio.master),
+            SettingRow::MusicVolume => percent(audio.music),
+            SettingRow::SfxVolume => percent(audio.sfx),
+            SettingRow::UiVolume => percent(audio.ui),
+            SettingRow::Mute => on_off(audio.muted),
+            SettingRow::ScreenShake => on_off(effects.screen_shake),
+            SettingRow::HitStop => on_off(effects.hit_stop),
+            SettingRow::MoveUp => format!("{:?}", controls.up),
+            SettingRow::MoveDown => format!("{:?}", controls.down),
+            SettingRow::MoveLeft => format!("{:?}", controls.left),
+            SettingRow::MoveRight => format!("{:?}", controls.right),
+            SettingRow::Restart => format!("{:?}", controls.restart),
+        };
+        // padded by characters, so columns only roughly line up
+        format!("{:<13} {}", localization.get(self.key()), value)
+    }
+
+    fn is_binding(&self) -> bool {
+        matches!(
+            self,
+            SettingRow::MoveUp
+                | SettingRow::MoveDown
+                | SettingRow::MoveLeft
+                | SettingRow::MoveRight
+                | SettingRow::Restart
+        )
+    }
+
+    // the key a binding row changes, None for everything else
+    fn binding<'a>(&self, controls: &'a mut Controls) -> Option<&'a mut KeyCode> {
+        match self {
+            SettingRow::MoveUp => Some(&mut controls.up),
+            SettingRow::MoveDown => Some(&mut controls.down),
+            SettingRow::MoveLeft => Some(&mut controls.left),
+            SettingRow::MoveRight => Some(&mut controls.right),
+            SettingRow::Restart => Some(&mut controls.restart),
+            _ => None,
+        }
+    }
+
+    // direction is -1 for left and 1 for right, toggles flip either way
+    fn adjust(&self, settings: &mut Settings, direction: f32) {
+        let step = |volume: &mut f32| {
+            *volume = ((*volume + direction * 0.1) * 10.0)
+                .round()
+                .clamp(0.0, 10.0)
+                / 10.0;
+        };
+        let toggle = |value: &mut bool| *value = !*value;
+        let video = &mut settings.video;
+        let audio = &mut settings.audio;
+        let effects = &mut settings.camera_effects;
+        match self {
+            SettingRow::Language => settings.language = settings.language.cycle(direction as i32),
+            SettingRow::Scale => {
+                let scale = video.scale as i32 + direction as i32;
+                video.scale = scale.clamp(1, MAX_SCALE as i32) as u32;
+            }
+            SettingRow::Upscale => {
+                video.upscale = match video.upscale {
+                    Upscale::Integer => Upscale::Fit,
+                    Upscale::Fit => Upscale::Integer,
+                }
+            }
+            SettingRow::Fullscreen => toggle(&mut video.fullscreen),
+            SettingRow::Vsync => toggle(&mut video.vsync),
+            SettingRow::MasterVolume => step(&mut audio.master),
+            SettingRow::MusicVolume => step(&mut audio.music),
+            SettingRow::SfxVolume => step(&mut audio.sfx),
+            SettingRow::UiVolume => step(&mut audio.ui),
+            SettingRow::Mute => toggle(&mut audio.muted),
+            SettingRow::ScreenShake => toggle(&mut effects.screen_shake),
+            SettingRow::HitStop => toggle(&mut effects.hit_stop),
+            // rebound by the next key press instead, see settings_menu_input
+            _ => {}
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct SettingsMenu {
+    open: bool,
+    selected: usize,
+    // waiting for the key to bind to the selected row
+    rebinding: bool,
+    // what's on disk, so closing only writes when something changed
+    saved: Settings,
+}
+
+#[derive(Component)]
+struct SettingsScreen;
+
+#[derive(Component)]
+struct SettingsText;
+
+// run condition for gameplay input, the menu takes the keyboard while it's open
+pub fn settings_closed(menu: Res<SettingsMenu>) -> bool {
+    !menu.open
+}
+
+fn toggle_settings_menu(
+    mut commands: Commands,
+    mut menu: ResMut<SettingsMenu>,
+    settings: Res<Settings>,
+    screens: Query<Entity, With<SettingsScreen>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut time: ResMut<Time>,
+    localization: Res<Localization>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Tab) || menu.rebinding {
+        return;
+    }
+    menu.open = !menu.open;
+    if !menu.open {
+        for entity in screens.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        if *settings != menu.saved {
+            settings.save();
+            menu.saved = settings.clone();
+        }
+        time.set_relative_speed(1.0);
+        return;
+    }
+
+    // the game waits while the menu is up, hit-stop pauses separately
+    time.set_relative_speed(0.0);
+    menu.selected = 0;
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            // above the level name card
+            z_index: ZIndex::Global(10),
+            ..Default::default()
+        })
+        .insert(SettingsScreen)
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: localization.bold_font(),
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(SettingsText);
+        });
+}
+
+fn settings_menu_input(
+    mut menu: ResMut<SettingsMenu>,
+    mut settings: ResMut<Settings>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut sounds: EventWriter<PlaySound>,
+) {
+    if !menu.open {
+        return;
+    }
+    let row = ROWS[menu.selected];
+    if menu.rebinding {
+        // escape quits the game and tab closes the menu, so neither can be bound
+        let Some(key) = keyboard_input
+            .get_just_pressed()
+            .find(|key| !matches!(key, KeyCode::Escape | KeyCode::Tab))
+        else {
+            return;
+        };
+        if let Some(binding) = row.binding(&mut settings.controls) {
+            *binding = *key;
+        }
+        menu.rebinding = false;
+    } else if keyboard_input.just_pressed(KeyCode::Up) {
+        menu.selected = (menu.selected + ROWS.len() - 1) % ROWS.len();
+        return;
+    } else if keyboard_input.just_pressed(KeyCode::Down) {
+        menu.selected = (menu.selected + 1) % ROWS.len();
+        return;
+    } else if keyboard_input.just_pressed(KeyCode::Return) && row.is_binding() {
+        menu.rebinding = true;
+        return;
+    } else if keyboard_input.just_pressed(KeyCode::Left) {
+        row.adjust(&mut settings, -1.0);
+    } else if keyboard_input.any_just_pressed([KeyCode::Right, KeyCode::Return]) {
+        row.adjust(&mut settings, 1.0);
+    } else {
+        return;
+    }
+    // lets you hear the new ui volume
+    sounds.send(PlaySound {
+        path: "sfx/step.ogg",
+        category: AudioCategory::Ui,
+    });
+}
+
+fn update_settings_text(
+    menu: Res<SettingsMenu>,
+    settings: Res<Settings>,
+    localization: Res<Localization>,
+    mut texts: Query<&mut Text, With<SettingsText>>,
+) {
+    for mut text in texts.iter_mut() {
+        let rows: Vec<String> = ROWS
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let cursor = if i == menu.selected { ">" } else { " " };
+                format!("{} {}", cursor, row.label(&settings, &localization))
+            })
+            .collect();
+        let hint = if menu.rebinding {
+            localization.get("settings.hint.rebinding")
+        } else {
+            localization.get("settings.hint")
+        };
+        let section = &mut text.sections[0];
+        section.value = format!(
+            "{}\n\n{}\n\n{}",
+            localization.get("settings.title"),
+            rows.join("\n"),
+            hint
+        );
+        // switching language can switch to the fallback font
+        section.style.font = localization.bold_font();
+    }
+}
+
+// main opens the window with the saved settings, after that only the video fields
+// that actually changed are applied, so a window resized by hand keeps its size
+// when some other setting changes. upscaling follows the window, see pixel_perfect.rs
+fn apply_video_settings(
+    settings: Res<Settings>,
+    mut applied: Local<Option<VideoSettings>>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let video = &settings.video;
+    let Some(previous) = applied.replace(video.clone()) else {
+        return;
+    };
+    let Ok(mut window) = windows.get_single_mut() else {
+        return;
+    };
+    if video.scale != previous.scale {
+        if video.fullscreen {
+            // picked up when going back to a window
+            if let Some(applied) = applied.as_mut() {
+                applied.scale = previous.scale;
+            }
+        } else {
+            let resolution = video.resolution();
+            window.resolution.set(resolution.x, resolution.y);
+        }
+    }
+    if video.fullscreen != previous.fullscreen {
+        window.mode = video.window_mode();
+    }
+    if video.vsync != previous.vsync {
+        window.present_mode = video.present_mode();
+    }
+}
+
+pub struct SettingsPlugin;
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        // main loads these early to open the window at the right size
+        let saved = app
+            .world
+            .get_resource_or_insert_with(Settings::load)
+            .clone();
+        app.insert_resource(SettingsMenu {
+            saved,
+            ..Default::default()
+        })
+        .add_system(toggle_settings_menu)
+        .add_system(settings_menu_input.after(toggle_settings_menu))
+        .add_system(update_settings_text.after(settings_menu_input))
+        .add_system(apply_video_settings.after(settings_menu_input));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_stay_in_range() {
+        let mut settings = Settings::default();
+        settings.audio.master = 0.95;
+        SettingRow::MasterVolume.adjust(&mut settings, 1.0);
+        assert_eq!(settings.audio.master, 1.0);
+        SettingRow::MasterVolume.adjust(&mut settings, 1.0);
+        assert_eq!(settings.audio.master, 1.0);
+        settings.audio.sfx = 0.05;
+        SettingRow::SfxVolume.adjust(&mut settings, -1.0);
+        assert_eq!(settings.audio.sfx, 0.0);
+        settings.video.scale = 1;
+        SettingRow::Scale.adjust(&mut settings, -1.0);
+        assert_eq!(settings.video.scale, 1);
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let mut settings = Settings::default();
+        settings.audio.muted = true;
+        settings.controls.up = KeyCode::W;
+        settings.camera_effects.hit_stop = false;
+        let file = ron::ser::to_string_pretty(&settings, ron::ser::PrettyConfig::new()).unwrap();
+        assert_eq!(ron::from_str::<Settings>(&file).unwrap(), settings);
+    }
+
+    #[test]
+    fn partial_file_keeps_defaults() {
+        let settings: Settings = ron::from_str("(video: (scale: 2))").unwrap();
+        assert_eq!(settings.video.scale, 2);
+        assert!(settings.video.vsync);
+        assert_eq!(settings.controls, Controls::default());
+    }
+}
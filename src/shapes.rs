@@ -0,0 +1,409 @@
+// collision shapes and separating axis tests between them
+// all shapes are centered on their parent's position plus an offset
+use bevy::prelude::Vec2;
+
+// result of an overlap test
+// normal points from the first shape towards the second,
+// move the first shape by -normal * depth to separate them
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Contact {
+    pub normal: Vec2,
+    pub depth: f32,
+}
+
+// the game goes through WorldShape, this is for comparing two shapes directly
+#[allow(dead_code)]
+pub trait CollidesWith<T> {
+    fn collides(&self, position: Vec2, other: &T, other_position: Vec2) -> Option<Contact>;
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Circle {
+    pub offset: Vec2, // position relative to parent
+    pub radius: f32,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Box {
+    pub offset: Vec2, // position relative to parent
+    pub size: Vec2,
+}
+impl Default for Box {
+    fn default() -> Self {
+        Box {
+            offset: Vec2::new(0.0, 0.0),
+            size: Vec2::new(1.0, 1.0),
+        }
+    }
+}
+
+// points are relative to offset and must wind in a consistent direction
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConvexPolygon {
+    pub offset: Vec2, // position relative to parent
+    pub points: Vec<Vec2>,
+}
+impl ConvexPolygon {
+    // box of the given size with each corner cut off by `chamfer` pixels
+    pub fn rounded_box(offset: Vec2, size: Vec2, chamfer: f32) -> Self {
+        let h = size / 2.0;
+        let c = chamfer.min(h.x).min(h.y);
+        ConvexPolygon {
+            offset,
+            points: vec![
+                Vec2::new(-h.x + c, -h.y),
+                Vec2::new(h.x - c, -h.y),
+                Vec2::new(h.x, -h.y + c),
+                Vec2::new(h.x, h.y - c),
+                Vec2::new(h.x - c, h.y),
+                Vec2::new(-h.x + c, h.y),
+                Vec2::new(-h.x, h.y - c),
+                Vec2::new(-h.x, -h.y + c),
+            ],
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Shape {
+    Circle(Circle),
+    Box(Box),
+    ConvexPolygon(ConvexPolygon),
+}
+impl Shape {
+    // size of the axis aligned box around the shape
+    pub fn size(&self) -> Vec2 {
+        match self {
+            Shape::Circle(circle) => Vec2::splat(circle.radius * 2.0),
+            Shape::Box(b) => b.size,
+            Shape::ConvexPolygon(polygon) => {
+                let (min, max) = polygon.points.iter().fold(
+                    (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+                    |(min, max), p| (min.min(*p), max.max(*p)),
+                );
+                (max - min).max(Vec2::ZERO)
+            }
+        }
+    }
+}
+
+// shape moved into world space
+pub enum WorldShape {
+    Circle { center: Vec2, radius: f32 },
+    Polygon(Vec<Vec2>),
+}
+impl WorldShape {
+    // corners of the axis aligned box around the shape
+    pub fn bounds(&self) -> (Vec2, Vec2) {
+        match self {
+            WorldShape::Circle { center, radius } => (
+                *center - Vec2::splat(*radius),
+                *center + Vec2::splat(*radius),
+            ),
+            WorldShape::Polygon(points) => points.iter().fold(
+                (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+                |(min, max), p| (min.min(*p), max.max(*p)),
+            ),
+        }
+    }
+
+    fn center(&self) -> Vec2 {
+        match self {
+            WorldShape::Circle { center, .. } => *center,
+            WorldShape::Polygon(points) => {
+                points.iter().copied().sum::<Vec2>() / points.len().max(1) as f32
+            }
+        }
+    }
+
+    fn project(&self, axis: Vec2) -> (f32, f32) {
+        match self {
+            WorldShape::Circle { center, radius } => {
+                let c = center.dot(axis);
+                (c - radius, c + radius)
+            }
+            WorldShape::Polygon(points) => points
+                .iter()
+                .map(|p| p.dot(axis))
+                .fold((f32::MAX, f32::MIN), |(min, max), d| {
+                    (min.min(d), max.max(d))
+                }),
+        }
+    }
+
+    fn axes(&self, other: &WorldShape) -> Vec<Vec2> {
+        match self {
+            // a circle only needs the axis towards the closest point of the other shape
+            WorldShape::Circle { center, .. } => {
+                let closest = match other {
+                    WorldShape::Circle { center, .. } => *center,
+                    WorldShape::Polygon(points) => points
+                        .iter()
+                        .copied()
+                        .min_by(|a, b| {
+                            a.distance_squared(*center)
+                                .total_cmp(&b.distance_squared(*center))
+                        })
+                        .unwrap_or(*center),
+                };
+                (closest - *center).try_normalize().into_iter().collect()
+            }
+            WorldShape::Polygon(points) => points
+                .iter()
+                .zip(points.iter().cycle().skip(1))
+                .filter_map(|(a, b)| (*b - *a).perp().try_normalize())
+                .collect(),
+        }
+    }
+}
+
+pub trait ToWorldShape {
+    fn to_world_shape(&self, position: Vec2) -> WorldShape;
+}
+impl ToWorldShape for Circle {
+    fn to_world_shape(&self, position: Vec2) -> WorldShape {
+        WorldShape::Circle {
+            center: position + self.offset,
+            radius: self.radius,
+        }
+    }
+}
+impl ToWorldShape for Box {
+    fn to_world_shape(&self, position: Vec2) -> WorldShape {
+        let center = position + self.offset;
+        let h = self.size / 2.0;
+        WorldShape::Polygon(vec![
+            center + Vec2::new(-h.x, -h.y),
+            center + Vec2::new(h.x, -h.y),
+            center + Vec2::new(h.x, h.y),
+            center + Vec2::new(-h.x, h.y),
+        ])
+    }
+}
+impl ToWorldShape for ConvexPolygon {
+    fn to_world_shape(&self, position: Vec2) -> WorldShape {
+        let center = position + self.offset;
+        WorldShape::Polygon(self.points.iter().map(|p| center + *p).collect())
+    }
+}
+impl ToWorldShape for Shape {
+    fn to_world_shape(&self, position: Vec2) -> WorldShape {
+        match self {
+            Shape::Circle(circle) => circle.to_world_shape(position),
+            Shape::Box(b) => b.to_world_shape(position),
+            Shape::ConvexPolygon(polygon) => polygon.to_world_shape(position),
+        }
+    }
+}
+
+// separating axis test, shapes that only touch edges don't collide
+pub fn overlap(a: &WorldShape, b: &WorldShape) -> Option<Contact> {
+    let mut contact: Option<Contact> = None;
+    for axis in a.axes(b).into_iter().chain(b.axes(a)) {
+        let (a_min, a_max) = a.project(axis);
+        let (b_min, b_max) = b.project(axis);
+        let depth = a_max.min(b_max) - a_min.max(b_min);
+        if depth <= 0.0 {
+            return None;
+        }
+        match contact {
+            Some(c) if c.depth <= depth => {}
+            _ => {
+                contact = Some(Contact {
+                    normal: axis,
+                    depth,
+                })
+            }
+        }
+    }
+
+    // two circles on top of each other have no axis to test
+    let mut contact = contact.unwrap_or(Contact {
+        normal: Vec2::Y,
+        depth: match (a, b) {
+            (WorldShape::Circle { radius: ra, .. }, WorldShape::Circle { radius: rb, .. }) => {
+                ra + rb
+            }
+            _ => 0.0,
+        },
+    });
+    if (b.center() - a.center()).dot(contact.normal) < 0.0 {
+        contact.normal = -contact.normal;
+    }
+    Some(contact)
+}
+
+// where in a step a moving box first touches a box that's standing still
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SweepHit {
+    // fraction of the step, 0.0 if they were already overlapping at the start
+    pub time: f32,
+    // points from the moving box towards the other one
+    pub normal: Vec2,
+    // fraction of the step they stop overlapping, past 1.0 if they still overlap at the end
+    pub exit: f32,
+}
+
+// swept test between the bounds of two boxes where a moves by `motion` over the step
+// for two moving boxes pass the difference of their motions
+pub fn sweep(a_min: Vec2, a_max: Vec2, motion: Vec2, b_min: Vec2, b_max: Vec2) -> Option<SweepHit> {
+    let mut enter = f32::MIN;
+    let mut exit = f32::MAX;
+    let mut normal = Vec2::ZERO;
+    for axis in 0..2 {
+        if motion[axis] == 0.0 {
+            // never overlaps on this axis
+            if a_max[axis] <= b_min[axis] || a_min[axis] >= b_max[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let t1 = (b_min[axis] - a_max[axis]) / motion[axis];
+        let t2 = (b_max[axis] - a_min[axis]) / motion[axis];
+        let (near, far) = (t1.min(t2), t1.max(t2));
+        if near > enter {
+            enter = near;
+            normal = Vec2::ZERO;
+            normal[axis] = motion[axis].signum();
+        }
+        exit = exit.min(far);
+    }
+
+    if enter >= exit || enter > 1.0 || exit <= 0.0 {
+        return None;
+    }
+    Some(SweepHit {
+        time: enter.max(0.0),
+        normal,
+        exit,
+    })
+}
+
+impl<S: ToWorldShape, T: ToWorldShape> CollidesWith<T> for S {
+    fn collides(&self, position: Vec2, other: &T, other_position: Vec2) -> Option<Contact> {
+        overlap(
+            &self.to_world_shape(position),
+            &other.to_world_shape(other_position),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> Box {
+        Box {
+            offset: Vec2::ZERO,
+            size: Vec2::new(8.0, 8.0),
+        }
+    }
+
+    #[test]
+    fn boxes_touching_do_not_collide() {
+        let b = unit_box();
+        assert_eq!(b.collides(Vec2::ZERO, &b, Vec2::new(8.0, 0.0)), None);
+    }
+
+    #[test]
+    fn boxes_overlap_along_shallowest_axis() {
+        let b = unit_box();
+        let contact = b.collides(Vec2::ZERO, &b, Vec2::new(6.0, 1.0)).unwrap();
+        assert_eq!(contact.normal, Vec2::X);
+        assert_eq!(contact.depth, 2.0);
+
+        let contact = b.collides(Vec2::new(6.0, 1.0), &b, Vec2::ZERO).unwrap();
+        assert_eq!(contact.normal, -Vec2::X);
+    }
+
+    #[test]
+    fn circles_overlap() {
+        let c = Circle {
+            offset: Vec2::ZERO,
+            radius: 2.0,
+        };
+        let contact = c.collides(Vec2::ZERO, &c, Vec2::new(0.0, 3.0)).unwrap();
+        assert_eq!(contact.normal, Vec2::Y);
+        assert_eq!(contact.depth, 1.0);
+        assert_eq!(c.collides(Vec2::ZERO, &c, Vec2::new(0.0, 4.0)), None);
+    }
+
+    #[test]
+    fn circle_misses_box_corner() {
+        let c = Circle {
+            offset: Vec2::ZERO,
+            radius: 1.0,
+        };
+        // inside the box's bounds on both axes but outside the corner
+        assert_eq!(
+            c.collides(Vec2::new(4.8, 4.8), &unit_box(), Vec2::ZERO),
+            None
+        );
+        assert!(c
+            .collides(Vec2::new(4.5, 0.0), &unit_box(), Vec2::ZERO)
+            .is_some());
+    }
+
+    #[test]
+    fn rounded_box_misses_corner() {
+        let car = ConvexPolygon::rounded_box(Vec2::ZERO, Vec2::new(14.0, 8.0), 2.0);
+        let b = unit_box();
+        // corners would overlap by half a pixel if the car were a plain box
+        assert_eq!(car.collides(Vec2::ZERO, &b, Vec2::new(10.5, 7.0)), None);
+        assert!(car.collides(Vec2::ZERO, &b, Vec2::new(10.0, 0.0)).is_some());
+    }
+
+    #[test]
+    fn sweep_catches_tunneling() {
+        // moves 20 pixels right, past a 7 pixel box that starts 6 pixels away
+        let hit = sweep(
+            Vec2::new(0.0, 0.0),
+            Vec2::new(14.0, 8.0),
+            Vec2::new(20.0, 0.0),
+            Vec2::new(20.0, 0.0),
+            Vec2::new(27.0, 8.0),
+        )
+        .unwrap();
+        assert_eq!(hit.time, 0.3);
+        assert_eq!(hit.normal, Vec2::X);
+        // still overlapping at the end of the step
+        assert_eq!(hit.exit, 1.35);
+    }
+
+    #[test]
+    fn sweep_misses() {
+        // wrong lane
+        assert_eq!(
+            sweep(
+                Vec2::new(0.0, 0.0),
+                Vec2::new(14.0, 8.0),
+                Vec2::new(20.0, 0.0),
+                Vec2::new(20.0, 8.0),
+                Vec2::new(27.0, 16.0),
+            ),
+            None
+        );
+        // doesn't get there this step
+        assert_eq!(
+            sweep(
+                Vec2::new(0.0, 0.0),
+                Vec2::new(14.0, 8.0),
+                Vec2::new(2.0, 0.0),
+                Vec2::new(20.0, 0.0),
+                Vec2::new(27.0, 8.0),
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn shape_size() {
+        let car = Shape::ConvexPolygon(ConvexPolygon::rounded_box(
+            Vec2::ZERO,
+            Vec2::new(14.0, 8.0),
+            2.0,
+        ));
+        assert_eq!(car.size(), Vec2::new(14.0, 8.0));
+    }
+}
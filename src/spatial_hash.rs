@@ -0,0 +1,107 @@
+// uniform grid of tile sized cells used to find what's near a position
+// without checking every entity
+use crate::consts::TILE_SIZE;
+use bevy::{prelude::*, utils::HashMap};
+
+// tile coordinates of the cell containing a pixel position
+pub fn cell(pixel: Vec2) -> IVec2 {
+    (pixel / TILE_SIZE as f32).floor().as_ivec2()
+}
+
+fn cells_between(min: IVec2, max: IVec2) -> impl Iterator<Item = IVec2> {
+    (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+}
+
+#[derive(Default, Resource)]
+pub struct SpatialHash {
+    cells: HashMap<IVec2, Vec<Entity>>,
+    // range of cells each entity was last inserted into
+    entity_cells: HashMap<Entity, (IVec2, IVec2)>,
+}
+impl SpatialHash {
+    // insert or move an entity covering the pixel bounds min..max
+    // does nothing if the entity is still in the same cells
+    pub fn update(&mut self, entity: Entity, min: Vec2, max: Vec2) {
+        let range = (cell(min), cell(max));
+        if self.entity_cells.get(&entity) == Some(&range) {
+            return;
+        }
+
+        self.remove(entity);
+        for c in cells_between(range.0, range.1) {
+            self.cells.entry(c).or_default().push(entity);
+        }
+        self.entity_cells.insert(entity, range);
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        if let Some((min, max)) = self.entity_cells.remove(&entity) {
+            for c in cells_between(min, max) {
+                if let Some(entities) = self.cells.get_mut(&c) {
+                    entities.retain(|e| *e != entity);
+                    if entities.is_empty() {
+                        self.cells.remove(&c);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.entity_cells.clear();
+    }
+
+    // entities in any cell touched by the pixel bounds min..max, without duplicates
+    pub fn query(&self, min: Vec2, max: Vec2) -> Vec<Entity> {
+        let mut entities: Vec<Entity> = cells_between(cell(min), cell(max))
+            .filter_map(|c| self.cells.get(&c))
+            .flatten()
+            .copied()
+            .collect();
+        entities.sort_unstable();
+        entities.dedup();
+        entities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_finds_nearby_entities_once() {
+        let mut hash = SpatialHash::default();
+        let wide = Entity::from_raw(0);
+        let far = Entity::from_raw(1);
+        hash.update(wide, Vec2::new(0.0, 0.0), Vec2::new(20.0, 4.0));
+        hash.update(far, Vec2::new(64.0, 64.0), Vec2::new(70.0, 70.0));
+
+        assert_eq!(
+            hash.query(Vec2::new(0.0, 0.0), Vec2::new(24.0, 8.0)),
+            vec![wide]
+        );
+        assert!(hash
+            .query(Vec2::new(32.0, 32.0), Vec2::new(40.0, 40.0))
+            .is_empty());
+    }
+
+    #[test]
+    fn update_moves_entity_between_cells() {
+        let mut hash = SpatialHash::default();
+        let car = Entity::from_raw(0);
+        hash.update(car, Vec2::new(0.0, 0.0), Vec2::new(7.0, 7.0));
+        hash.update(car, Vec2::new(16.0, 0.0), Vec2::new(23.0, 7.0));
+
+        assert!(hash
+            .query(Vec2::new(0.0, 0.0), Vec2::new(7.0, 7.0))
+            .is_empty());
+        assert_eq!(
+            hash.query(Vec2::new(16.0, 0.0), Vec2::new(16.0, 0.0)),
+            vec![car]
+        );
+
+        hash.remove(car);
+        assert!(hash.cells.is_empty());
+    }
+}
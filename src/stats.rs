@@ -0,0 +1,289 @@
+use crate::car::Car;
+use crate::collisions::{CollisionEvent, CollisionPhase};
+use crate::consts::{AppState, SystemLabels};
+use crate::map::{CurrentLevel, Levels};
+use crate::player::Player;
+use crate::transitions::no_transition;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// for each try at a level, running out starts the level over
+pub const LIVES: u32 = 3;
+// written next to settings.ron
+const PERSONAL_BESTS_PATH: &str = "personal_bests.ron";
+
+#[derive(Resource)]
+pub struct LevelStats {
+    // secs of game time since the level was revealed
+    pub elapsed: f32,
+    pub hits: u32,
+    pub lives: u32,
+}
+impl Default for LevelStats {
+    fn default() -> Self {
+        LevelStats {
+            elapsed: 0.0,
+            hits: 0,
+            lives: LIVES,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Medal {
+    Gold,
+    Silver,
+    Bronze,
+}
+impl Medal {
+    // gold for beating par without getting hit, silver for one or the other
+    pub fn award(time: f32, hits: u32, par_time: f32) -> Medal {
+        match (time <= par_time, hits == 0) {
+            (true, true) => Medal::Gold,
+            (true, false) | (false, true) => Medal::Silver,
+            (false, false) => Medal::Bronze,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LevelResult {
+    pub time: f32,
+    pub hits: u32,
+    pub medal: Medal,
+    // faster than any time saved before it
+    pub new_best: bool,
+}
+
+// how the current run through the levels went, by level index
+#[derive(Resource, Default)]
+pub struct CampaignStats {
+    pub results: Vec<Option<LevelResult>>,
+    pub new_best_total: bool,
+}
+impl CampaignStats {
+    // only once every level has a time
+    pub fn total_time(&self, level_count: usize) -> Option<f32> {
+        if self.results.len() < level_count {
+            return None;
+        }
+        self.results
+            .iter()
+            .map(|result| result.map(|result| result.time))
+            .sum()
+    }
+}
+
+// fastest times by level path, kept between runs
+#[derive(Serialize, Deserialize, Resource, Default, Debug)]
+#[serde(default)]
+pub struct PersonalBests {
+    pub levels: HashMap<String, f32>,
+    pub campaign: Option<f32>,
+}
+impl PersonalBests {
+    // missing or broken files start over with no records
+    fn load() -> Self {
+        let Ok(file) = std::fs::read_to_string(PERSONAL_BESTS_PATH) else {
+            return PersonalBests::default();
+        };
+        ron::from_str(&file).unwrap_or_else(|e| {
+            warn!("couldn't parse {}: {}", PERSONAL_BESTS_PATH, e);
+            PersonalBests::default()
+        })
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .map_err(|e| e.to_string())
+            .and_then(|file| std::fs::write(PERSONAL_BESTS_PATH, file).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            warn!("couldn't save {}: {}", PERSONAL_BESTS_PATH, e);
+        }
+    }
+}
+
+fn beats(record: Option<f32>, time: f32) -> bool {
+    match record {
+        Some(best) => time < best,
+        None => true,
+    }
+}
+
+fn reset_level_stats(mut stats: ResMut<LevelStats>) {
+    *stats = LevelStats::default();
+}
+
+// game time, so hit-stop and the settings menu don't count
+fn tick_level_time(mut stats: ResMut<LevelStats>, time: Res<Time>) {
+    stats.elapsed += time.delta_seconds();
+}
+
+fn count_hits(
+    mut events: EventReader<CollisionEvent<Player, Car>>,
+    mut stats: ResMut<LevelStats>,
+    mut levels: ResMut<Levels>,
+    mut state: ResMut<NextState<AppState>>,
+) {
+    // the player can't be hit again while rolling home, so this is one hit.
+    // counted instead of any() so the reader is drained and leftovers aren't seen next frame
+    let started = events
+        .iter()
+        .filter(|ev| ev.phase == CollisionPhase::Started)
+        .count();
+    if started == 0 {
+        return;
+    }
+    stats.hits += 1;
+    stats.lives = stats.lives.saturating_sub(1);
+    if stats.lives == 0 {
+        levels.retry = true;
+        state.set(AppState::LevelDone);
+    }
+}
+
+fn record_level_result(
+    stats: Res<LevelStats>,
+    levels: Res<Levels>,
+    current_level: Res<CurrentLevel>,
+    mut campaign: ResMut<CampaignStats>,
+    mut bests: ResMut<PersonalBests>,
+) {
+    // ran out of lives, nothing to record
+    if levels.retry {
+        return;
+    }
+    let path = &levels.levels[levels.current_level];
+    let new_best = beats(bests.levels.get(path).copied(), stats.elapsed);
+    if new_best {
+        bests.levels.insert(path.clone(), stats.elapsed);
+        bests.save();
+    }
+
+    let index = levels.current_level;
+    if campaign.results.len() <= index {
+        campaign.results.resize(index + 1, None);
+    }
+    campaign.results[index] = Some(LevelResult {
+        time: stats.elapsed,
+        hits: stats.hits,
+        medal: Medal::award(stats.elapsed, stats.hits, current_level.0.par_time),
+        new_best,
+    });
+}
+
+fn record_campaign(
+    levels: Res<Levels>,
+    mut campaign: ResMut<CampaignStats>,
+    mut bests: ResMut<PersonalBests>,
+) {
+    // replaying one level doesn't make a new campaign time
+    if levels.single_level {
+        return;
+    }
+    let Some(total) = campaign.total_time(levels.levels.len()) else {
+        return;
+    };
+    campaign.new_best_total = beats(bests.campaign, total);
+    if campaign.new_best_total {
+        bests.campaign = Some(total);
+        bests.save();
+    }
+}
+
+pub struct StatsPlugin;
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelStats>()
+            .init_resource::<CampaignStats>()
+            .insert_resource(PersonalBests::load())
+            .add_system(reset_level_stats.in_schedule(OnEnter(AppState::Loading)))
+            .add_system(
+                tick_level_time
+                    .run_if(no_transition)
+                    .in_set(OnUpdate(AppState::InGame)),
+            )
+            // on the fixed step with the collisions it counts, so none are missed
+            // when a slow frame runs several steps
+            .add_system(
+                count_hits
+                    .run_if(in_state(AppState::InGame))
+                    .after(SystemLabels::Collisions)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(record_level_result.in_schedule(OnEnter(AppState::LevelDone)))
+            .add_system(record_campaign.in_schedule(OnEnter(AppState::Finished)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collisions::CollisionData;
+    use crate::shapes::Contact;
+
+    #[test]
+    fn medals() {
+        assert_eq!(Medal::award(9.0, 0, 10.0), Medal::Gold);
+        assert_eq!(Medal::award(9.0, 1, 10.0), Medal::Silver);
+        assert_eq!(Medal::award(11.0, 0, 10.0), Medal::Silver);
+        assert_eq!(Medal::award(11.0, 2, 10.0), Medal::Bronze);
+    }
+
+    #[test]
+    fn total_needs_every_level() {
+        let result = |time| {
+            Some(LevelResult {
+                time,
+                hits: 0,
+                medal: Medal::Gold,
+                new_best: false,
+            })
+        };
+        let mut campaign = CampaignStats {
+            results: vec![result(1.0), None, result(2.0)],
+            new_best_total: false,
+        };
+        assert_eq!(campaign.total_time(3), None);
+        campaign.results[1] = result(3.0);
+        assert_eq!(campaign.total_time(3), Some(6.0));
+        assert_eq!(campaign.total_time(4), None);
+    }
+
+    // sent mid-frame like the fixed update does, so a leftover would still be readable next frame
+    fn hit_twice(mut events: EventWriter<CollisionEvent<Player, Car>>, mut sent: Local<bool>) {
+        if *sent {
+            return;
+        }
+        *sent = true;
+        for car in 1..=2 {
+            events.send(CollisionEvent::new(CollisionData {
+                entity: Entity::from_raw(0),
+                collided_with: Entity::from_raw(car),
+                collision: Contact {
+                    normal: Vec2::X,
+                    depth: 1.0,
+                },
+                time_of_impact: 0.0,
+                phase: CollisionPhase::Started,
+            }));
+        }
+    }
+
+    #[test]
+    fn two_starts_in_one_update_are_one_hit() {
+        let mut app = App::new();
+        app.add_state::<AppState>()
+            .add_event::<CollisionEvent<Player, Car>>()
+            .init_resource::<LevelStats>()
+            .init_resource::<Levels>()
+            .add_system(hit_twice.before(count_hits))
+            .add_system(count_hits);
+        app.update();
+        app.update();
+        let stats = app.world.resource::<LevelStats>();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.lives, LIVES - 1);
+    }
+}
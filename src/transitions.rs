@@ -0,0 +1,365 @@
+use crate::consts::{AppState, SCREEN_X_MAX, SCREEN_Y_MAX};
+use crate::coordinates::{KeepBetweenLevels, PixelPosition};
+use crate::localization::Localization;
+use crate::map::Levels;
+use crate::player::Player;
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+
+// secs, counted in real time so hit-stop doesn't hold a transition up
+const COVER_TIME: f32 = 0.4;
+const HOLD_TIME: f32 = 0.8;
+const REVEAL_TIME: f32 = 0.4;
+
+// drawn over everything but the ui
+const OVERLAY_Z: f32 = 50.0;
+const DISSOLVE_BLOCK: f32 = 4.0;
+const OVERLAY_COLOR: Color = Color::BLACK;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TransitionEffect {
+    Fade,
+    // a circle closing in on the player
+    Iris,
+    // blocks blinking in at random
+    Dissolve,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Phase {
+    Idle,
+    Covering,
+    // fully covered, waiting for something to call reveal
+    Covered,
+    // fully covered with the level name showing
+    Holding,
+    Revealing,
+}
+
+#[derive(Resource)]
+pub struct ScreenTransition {
+    pub effect: TransitionEffect,
+    phase: Phase,
+    // 0 is the game fully showing, 1 is fully covered
+    coverage: f32,
+    hold_left: f32,
+    // changes effect each level
+    count: usize,
+}
+impl Default for ScreenTransition {
+    // the game starts out covered and reveals the first level
+    fn default() -> Self {
+        ScreenTransition {
+            effect: TransitionEffect::Fade,
+            phase: Phase::Covered,
+            coverage: 1.0,
+            hold_left: 0.0,
+            count: 0,
+        }
+    }
+}
+impl ScreenTransition {
+    pub fn cover(&mut self) {
+        if matches!(
+            self.phase,
+            Phase::Covering | Phase::Covered | Phase::Holding
+        ) {
+            return;
+        }
+        self.effect = [
+            TransitionEffect::Fade,
+            TransitionEffect::Iris,
+            TransitionEffect::Dissolve,
+        ][self.count % 3];
+        self.count += 1;
+        // picks up from wherever a reveal got to
+        self.phase = Phase::Covering;
+    }
+
+    fn reveal(&mut self, hold: f32) {
+        self.hold_left = hold;
+        self.phase = Phase::Holding;
+    }
+
+    pub fn is_covered(&self) -> bool {
+        self.phase == Phase::Covered
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.phase == Phase::Idle
+    }
+}
+
+// run condition for systems that should wait until the screen is fully covered
+pub fn screen_covered(transition: Res<ScreenTransition>) -> bool {
+    transition.is_covered()
+}
+
+// run condition that blocks input while a transition plays
+pub fn no_transition(transition: Res<ScreenTransition>) -> bool {
+    transition.is_idle()
+}
+
+#[derive(Component)]
+struct FadeOverlay;
+
+// one pixel row of the iris, either the part left or right of the circle
+#[derive(Component)]
+struct IrisStrip {
+    row: f32,
+    right: bool,
+}
+
+#[derive(Component)]
+struct DissolveBlock {
+    // shows once coverage passes this
+    threshold: f32,
+}
+
+#[derive(Component)]
+struct LevelNameCard;
+
+fn overlay_sprite(size: Vec2, translation: Vec2) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color: OVERLAY_COLOR,
+            custom_size: Some(size),
+            ..Default::default()
+        },
+        transform: Transform::from_translation(translation.extend(OVERLAY_Z)),
+        visibility: Visibility::Hidden,
+        ..Default::default()
+    }
+}
+
+fn setup_overlays(mut commands: Commands) {
+    let screen = Vec2::new(SCREEN_X_MAX as f32, SCREEN_Y_MAX as f32);
+    commands.spawn((
+        overlay_sprite(screen, screen / 2.0),
+        FadeOverlay,
+        KeepBetweenLevels,
+    ));
+
+    for row in 0..SCREEN_Y_MAX {
+        for right in [false, true] {
+            commands.spawn((
+                overlay_sprite(Vec2::ZERO, Vec2::ZERO),
+                IrisStrip {
+                    row: row as f32,
+                    right,
+                },
+                KeepBetweenLevels,
+            ));
+        }
+    }
+
+    let columns = (screen.x / DISSOLVE_BLOCK) as usize;
+    let rows = (screen.y / DISSOLVE_BLOCK) as usize;
+    let mut order: Vec<usize> = (0..columns * rows).collect();
+    order.shuffle(&mut rand::thread_rng());
+    for (i, position) in order.into_iter().enumerate() {
+        let block = Vec2::new((i % columns) as f32, (i / columns) as f32);
+        commands.spawn((
+            overlay_sprite(Vec2::splat(DISSOLVE_BLOCK), (block + 0.5) * DISSOLVE_BLOCK),
+            DissolveBlock {
+                threshold: (position as f32 + 0.5) / (columns * rows) as f32,
+            },
+            KeepBetweenLevels,
+        ));
+    }
+}
+
+fn start_cover(mut transition: ResMut<ScreenTransition>) {
+    transition.cover();
+}
+
+fn reveal_level(
+    mut commands: Commands,
+    mut transition: ResMut<ScreenTransition>,
+    levels: Res<Levels>,
+    localization: Res<Localization>,
+) {
+    transition.reveal(HOLD_TIME);
+    let font = localization.bold_font();
+    let style = |font_size| TextStyle {
+        font: font.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(LevelNameCard)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                localization.format("card.level", &[("level", &(levels.current_level + 1))]),
+                style(32.0),
+            ));
+            parent.spawn(TextBundle::from_section(
+                localization.level_name(&levels, levels.current_level),
+                style(16.0),
+            ));
+        });
+}
+
+fn reveal_screen(mut transition: ResMut<ScreenTransition>) {
+    transition.reveal(0.0);
+}
+
+fn update_transition(
+    mut commands: Commands,
+    mut transition: ResMut<ScreenTransition>,
+    cards: Query<(Entity, &Children), With<LevelNameCard>>,
+    mut texts: Query<&mut Text>,
+    time: Res<Time>,
+) {
+    let delta = time.raw_delta_seconds();
+    match transition.phase {
+        Phase::Idle | Phase::Covered => {}
+        Phase::Covering => {
+            transition.coverage = (transition.coverage + delta / COVER_TIME).min(1.0);
+            if transition.coverage >= 1.0 {
+                transition.phase = Phase::Covered;
+            }
+        }
+        Phase::Holding => {
+            transition.hold_left -= delta;
+            if transition.hold_left <= 0.0 {
+                transition.phase = Phase::Revealing;
+            }
+        }
+        Phase::Revealing => {
+            transition.coverage = (transition.coverage - delta / REVEAL_TIME).max(0.0);
+            if transition.coverage <= 0.0 {
+                transition.phase = Phase::Idle;
+            }
+        }
+    }
+
+    // the name card fades out with the reveal
+    for (card, children) in cards.iter() {
+        if transition.phase == Phase::Idle {
+            commands.entity(card).despawn_recursive();
+            continue;
+        }
+        let alpha = if transition.phase == Phase::Revealing {
+            transition.coverage
+        } else {
+            1.0
+        };
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                for section in text.sections.iter_mut() {
+                    section.style.color.set_a(alpha);
+                }
+            }
+        }
+    }
+}
+
+fn visible_if(visible: bool) -> Visibility {
+    if visible {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    }
+}
+
+fn draw_fade(
+    transition: Res<ScreenTransition>,
+    mut query: Query<(&mut Sprite, &mut Visibility), With<FadeOverlay>>,
+) {
+    for (mut sprite, mut visibility) in query.iter_mut() {
+        visibility.set_if_neq(visible_if(
+            transition.effect == TransitionEffect::Fade && transition.coverage > 0.0,
+        ));
+        if sprite.color.a() != transition.coverage {
+            sprite.color.set_a(transition.coverage);
+        }
+    }
+}
+
+fn draw_iris(
+    transition: Res<ScreenTransition>,
+    mut query: Query<(&IrisStrip, &mut Sprite, &mut Transform, &mut Visibility)>,
+    player_query: Query<&PixelPosition, With<Player>>,
+) {
+    let screen = Vec2::new(SCREEN_X_MAX as f32, SCREEN_Y_MAX as f32);
+    let center = player_query
+        .get_single()
+        .map_or(screen / 2.0, |position| position.0 + Vec2::new(4.0, 4.0))
+        .round();
+    // big enough to uncover the furthest corner
+    let max_radius = [
+        Vec2::ZERO,
+        Vec2::new(screen.x, 0.0),
+        Vec2::new(0.0, screen.y),
+        screen,
+    ]
+    .iter()
+    .map(|corner| corner.distance(center))
+    .fold(0.0, f32::max);
+    let radius = (1.0 - transition.coverage) * max_radius;
+    let active = transition.effect == TransitionEffect::Iris && transition.coverage > 0.0;
+
+    for (strip, mut sprite, mut transform, mut visibility) in query.iter_mut() {
+        let dy = strip.row + 0.5 - center.y;
+        let half_chord = (radius * radius - dy * dy).max(0.0).sqrt().round();
+        let (left, right) = if strip.right {
+            ((center.x + half_chord).clamp(0.0, screen.x), screen.x)
+        } else {
+            (0.0, (center.x - half_chord).clamp(0.0, screen.x))
+        };
+        let width = right - left;
+        visibility.set_if_neq(visible_if(active && width > 0.0));
+        // hidden strips keep whatever size they had
+        if !active || width <= 0.0 {
+            continue;
+        }
+        let size = Some(Vec2::new(width, 1.0));
+        if sprite.custom_size != size {
+            sprite.custom_size = size;
+        }
+        let translation = Vec3::new(left + width / 2.0, strip.row + 0.5, OVERLAY_Z);
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+    }
+}
+
+fn draw_dissolve(
+    transition: Res<ScreenTransition>,
+    mut query: Query<(&DissolveBlock, &mut Visibility)>,
+) {
+    let active = transition.effect == TransitionEffect::Dissolve;
+    for (block, mut visibility) in query.iter_mut() {
+        visibility.set_if_neq(visible_if(active && transition.coverage >= block.threshold));
+    }
+}
+
+pub struct TransitionPlugin;
+impl Plugin for TransitionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScreenTransition>()
+            .add_startup_system(setup_overlays)
+            .add_system(start_cover.in_schedule(OnEnter(AppState::LevelDone)))
+            .add_system(reveal_level.in_schedule(OnEnter(AppState::InGame)))
+            .add_system(reveal_screen.in_schedule(OnEnter(AppState::Finished)))
+            .add_system(update_transition)
+            // the overlays only change while a transition plays, and only the
+            // sprites that actually change are written so the rest stay out of extraction
+            .add_systems(
+                (draw_fade, draw_iris, draw_dissolve)
+                    .distributive_run_if(resource_changed::<ScreenTransition>())
+                    .after(update_transition),
+            );
+    }
+}
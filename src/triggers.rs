@@ -0,0 +1,155 @@
+use crate::collisions::{
+    AddCollisionPair, CollisionEvent, CollisionLayers, CollisionPhase, Hitbox,
+};
+use crate::consts::{AppState, SystemLabels, TILE_SIZE};
+use crate::coordinates::{SpriteSize, TilePosition};
+use crate::player::Player;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TriggerKind {
+    // finishes the level
+    Goal,
+    // player respawns here after getting hit
+    Checkpoint,
+}
+
+// rectangle of tiles from the map file, tile_x and tile_y are the bottom left tile
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct TriggerData {
+    pub tile_x: f32,
+    pub tile_y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub kind: TriggerKind,
+}
+
+#[derive(Component)]
+pub struct TriggerZone {
+    pub kind: TriggerKind,
+    pub tile_position: TilePosition,
+}
+
+// sent once each time a player walks into a zone
+pub struct TriggerEntered {
+    pub player: Entity,
+    pub kind: TriggerKind,
+    pub tile_position: TilePosition,
+}
+
+pub fn spawn_trigger_zone(commands: &mut Commands, data: TriggerData) {
+    let tile_position = TilePosition(Vec2::new(data.tile_x, data.tile_y));
+    let size = Vec2::new(data.width, data.height) * TILE_SIZE as f32;
+    commands.spawn((
+        TriggerZone {
+            kind: data.kind,
+            tile_position,
+        },
+        tile_position.get_pixel_position(),
+        SpriteSize(size),
+        Hitbox::new(Vec2::new(0.0, 0.0), size)
+            .with_layers(CollisionLayers::TRIGGER, CollisionLayers::PLAYER),
+    ));
+}
+
+pub fn player_enters_zone(
+    mut event_reader: EventReader<CollisionEvent<Player, TriggerZone>>,
+    mut ev_trigger: EventWriter<TriggerEntered>,
+    zones: Query<&TriggerZone>,
+) {
+    for ev in event_reader.iter() {
+        if ev.phase != CollisionPhase::Started {
+            continue;
+        }
+        if let Ok(zone) = zones.get(ev.collided_with) {
+            ev_trigger.send(TriggerEntered {
+                player: ev.entity,
+                kind: zone.kind,
+                tile_position: zone.tile_position,
+            });
+        }
+    }
+}
+
+pub struct TriggerPlugin;
+impl Plugin for TriggerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_collision_pair::<Player, TriggerZone>()
+            .add_event::<TriggerEntered>()
+            .add_system(
+                player_enters_zone
+                    .run_if(in_state(AppState::InGame))
+                    .after(SystemLabels::Collisions)
+                    .before(SystemLabels::PlayerMovement)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collisions::{CollisionPlugin, Hurtbox};
+    use crate::coordinates::{PixelPosition, PreviousPixelPosition};
+    use crate::map::BusStop;
+    use bevy::ecs::system::CommandQueue;
+
+    // pixel rows where the player first touched the goal, walking one pixel per step
+    fn entered_at(from: f32, to: f32) -> Vec<f32> {
+        let mut app = App::new();
+        app.add_state::<AppState>()
+            .add_plugin(CollisionPlugin)
+            .add_plugin(TriggerPlugin);
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::InGame);
+        app.update();
+
+        let stop = BusStop {
+            tile_x: 7.0,
+            tile_y: 1.0,
+        };
+        let mut queue = CommandQueue::default();
+        spawn_trigger_zone(&mut Commands::new(&mut queue, &app.world), stop.goal_zone());
+        queue.apply(&mut app.world);
+        let start = Vec2::new(7.0 * TILE_SIZE as f32, from);
+        let player = app
+            .world
+            .spawn((
+                Player,
+                PixelPosition(start),
+                PreviousPixelPosition(start),
+                SpriteSize(Vec2::splat(TILE_SIZE as f32)),
+                Hurtbox::new(Vec2::new(-0.5, 0.0), Vec2::new(7.0, 8.0))
+                    .with_layers(CollisionLayers::PLAYER, CollisionLayers::TRIGGER),
+            ))
+            .id();
+
+        let mut entered = vec![];
+        let mut y = from;
+        while y != to {
+            let previous = y;
+            y += (to - from).signum();
+            app.world
+                .get_mut::<PreviousPixelPosition>(player)
+                .unwrap()
+                .0
+                .y = previous;
+            app.world.get_mut::<PixelPosition>(player).unwrap().0.y = y;
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+            let mut events = app.world.resource_mut::<Events<TriggerEntered>>();
+            entered.extend(events.drain().map(|_| y));
+        }
+        entered
+    }
+
+    #[test]
+    fn goal_is_the_row_above_the_stop() {
+        let tile = TILE_SIZE as f32;
+        // walking down from the house, touched as soon as the move from row 3 into row 2 starts
+        assert_eq!(entered_at(4.0 * tile, 0.0), vec![3.0 * tile - 1.0]);
+        // the stop's own row doesn't count
+        assert!(entered_at(0.0, 1.0 * tile).is_empty());
+    }
+}
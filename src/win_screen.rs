@@ -1,13 +1,105 @@
 use crate::consts::AppState;
+use crate::localization::Localization;
 use crate::map::Levels;
+use crate::settings::{settings_closed, Settings};
+use crate::stats::{CampaignStats, Medal};
+use crate::transitions::{no_transition, screen_covered, ScreenTransition};
 use bevy::prelude::*;
 
+const TEXT_COLOR: Color = Color::rgb(0.37, 0.34, 0.31);
+// sand, so the table sits on the beach
+const PANEL_COLOR: Color = Color::rgba(0.96, 0.89, 0.72, 0.9);
+const SELECTED_COLOR: Color = Color::rgba(0.37, 0.34, 0.31, 0.2);
+// widths of the level, time, hits, medal and best columns
+const COLUMNS: [f32; 5] = [200.0, 50.0, 60.0, 60.0, 50.0];
+
 #[derive(Component)]
 struct RootNode;
 
 #[derive(Component)]
 struct VictoryScreen;
-fn spawn_end_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+enum VictoryButton {
+    ReplayLevel(usize),
+    PlayAgain,
+}
+impl VictoryButton {
+    // the level rows, then play again under them
+    fn index(&self, level_count: usize) -> usize {
+        match self {
+            VictoryButton::ReplayLevel(level) => *level,
+            VictoryButton::PlayAgain => level_count,
+        }
+    }
+}
+
+// index into the buttons, top to bottom
+#[derive(Resource, Default)]
+struct VictoryMenu {
+    selected: usize,
+}
+
+// m:ss.s
+fn format_time(secs: f32) -> String {
+    let tenths = (secs * 10.0) as u32;
+    format!("{}:{:02}.{}", tenths / 600, tenths / 10 % 60, tenths % 10)
+}
+
+fn medal_text(medal: Medal, localization: &Localization) -> (String, Color) {
+    let (key, color) = match medal {
+        Medal::Gold => ("medal.gold", Color::rgb(0.85, 0.62, 0.1)),
+        Medal::Silver => ("medal.silver", Color::rgb(0.55, 0.57, 0.6)),
+        Medal::Bronze => ("medal.bronze", Color::rgb(0.6, 0.36, 0.2)),
+    };
+    (localization.get(key).to_string(), color)
+}
+
+fn spawn_row(
+    parent: &mut ChildBuilder,
+    button: Option<VictoryButton>,
+    cells: [(String, Color); 5],
+    font: &Handle<Font>,
+) {
+    let row = NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Row,
+            padding: UiRect::new(Val::Px(4.0), Val::Px(4.0), Val::Px(2.0), Val::Px(2.0)),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut row = parent.spawn(row);
+    if let Some(button) = button {
+        row.insert((Interaction::default(), button));
+    }
+    row.with_children(|row| {
+        for ((text, color), width) in cells.into_iter().zip(COLUMNS) {
+            row.spawn(TextBundle {
+                style: Style {
+                    size: Size::width(Val::Px(width)),
+                    ..Default::default()
+                },
+                text: Text::from_section(
+                    text,
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 16.0,
+                        color,
+                    },
+                ),
+                ..Default::default()
+            });
+        }
+    });
+}
+
+fn spawn_end_screen(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    levels: Res<Levels>,
+    mut menu: ResMut<VictoryMenu>,
+) {
     let handle = asset_server.load("sprites/victory_screen.png");
     commands
         .spawn(SpriteBundle {
@@ -20,84 +112,262 @@ fn spawn_end_screen(mut commands: Commands, asset_server: Res<AssetServer>) {
         })
         .insert(VictoryScreen);
 
+    // back on the level that was just replayed, or play again after a full run
+    menu.selected = if levels.single_level {
+        levels.current_level
+    } else {
+        levels.levels.len()
+    };
+}
+
+// separate from the background so it can be rebuilt in another language
+fn spawn_victory_panel(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    levels: Res<Levels>,
+    campaign: Res<CampaignStats>,
+    localization: Res<Localization>,
+) {
+    let title_font = localization.bold_font();
+    let font = localization.font();
+    let text = |text: &str| (text.to_string(), TEXT_COLOR);
+    let best = localization.get("win.best");
     commands
         .spawn(NodeBundle {
             style: Style {
                 size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
-                flex_direction: FlexDirection::ColumnReverse,
                 justify_content: JustifyContent::Center,
-                align_content: AlignContent::Center,
                 align_items: AlignItems::Center,
                 ..Default::default()
             },
-            background_color: Color::rgba(0.0, 0.0, 0.0, 0.0).into(),
             ..Default::default()
         })
         .insert(RootNode)
         .with_children(|parent| {
-            parent.spawn(TextBundle {
-                style: Style {
-                    margin: UiRect::all(Val::Px(5.0)),
-                    ..Default::default()
-                },
-                text: Text::from_section(
-                    "You Win!",
-                    TextStyle {
-                        font: asset_server.load("fonts/silkscreen/slkscreb.ttf"),
-                        font_size: 40.0,
-                        color: Color::rgb(0.37, 0.34, 0.31),
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(8.0)),
+                        ..Default::default()
                     },
-                ),
-                ..Default::default()
-            });
-
-            parent.spawn(TextBundle {
-                style: Style {
-                    margin: UiRect::all(Val::Px(5.0)),
+                    background_color: PANEL_COLOR.into(),
                     ..Default::default()
-                },
-                text: Text::from_section(
-                    "Press X to Restart",
-                    TextStyle {
-                        font: asset_server.load("fonts/silkscreen/slkscreb.ttf"),
-                        font_size: 16.0,
-                        color: Color::rgb(0.37, 0.34, 0.31),
-                    },
-                )
-                .with_alignment(TextAlignment::Center),
-                ..Default::default()
-            });
+                })
+                .with_children(|panel| {
+                    panel.spawn(
+                        TextBundle::from_section(
+                            localization.get("win.title"),
+                            TextStyle {
+                                font: title_font.clone(),
+                                font_size: 40.0,
+                                color: TEXT_COLOR,
+                            },
+                        )
+                        .with_style(Style {
+                            margin: UiRect::all(Val::Px(5.0)),
+                            ..Default::default()
+                        }),
+                    );
+
+                    for index in 0..levels.levels.len() {
+                        let name = text(&format!(
+                            "{} {}",
+                            index + 1,
+                            localization.level_name(&levels, index)
+                        ));
+                        let result = campaign.results.get(index).copied().flatten();
+                        let cells = match result {
+                            Some(result) => [
+                                name,
+                                text(&format_time(result.time)),
+                                text(&localization.format("win.hits", &[("hits", &result.hits)])),
+                                medal_text(result.medal, &localization),
+                                text(if result.new_best { best } else { "" }),
+                            ],
+                            None => [name, text("--"), text(""), text(""), text("")],
+                        };
+                        spawn_row(panel, Some(VictoryButton::ReplayLevel(index)), cells, &font);
+                    }
+
+                    let total = campaign.total_time(levels.levels.len());
+                    spawn_row(
+                        panel,
+                        None,
+                        [
+                            text(localization.get("win.total")),
+                            text(&total.map_or("--".to_string(), format_time)),
+                            text(""),
+                            text(""),
+                            text(if campaign.new_best_total { best } else { "" }),
+                        ],
+                        &font,
+                    );
+
+                    panel
+                        .spawn(ButtonBundle {
+                            style: Style {
+                                margin: UiRect::top(Val::Px(8.0)),
+                                padding: UiRect::new(
+                                    Val::Px(8.0),
+                                    Val::Px(8.0),
+                                    Val::Px(2.0),
+                                    Val::Px(2.0),
+                                ),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        })
+                        .insert(VictoryButton::PlayAgain)
+                        .with_children(|button| {
+                            button.spawn(TextBundle::from_section(
+                                localization.get("win.play_again"),
+                                TextStyle {
+                                    font: title_font.clone(),
+                                    font_size: 16.0,
+                                    color: TEXT_COLOR,
+                                },
+                            ));
+                        });
+
+                    panel.spawn(
+                        TextBundle::from_section(
+                            localization.format(
+                                "win.hint",
+                                &[("restart", &format!("{:?}", settings.controls.restart))],
+                            ),
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 16.0,
+                                color: TEXT_COLOR,
+                            },
+                        )
+                        .with_style(Style {
+                            margin: UiRect::top(Val::Px(5.0)),
+                            ..Default::default()
+                        }),
+                    );
+                });
         });
 }
 
-fn restart(
-    mut state: ResMut<NextState<AppState>>,
+fn victory_menu_input(
+    mut menu: ResMut<VictoryMenu>,
+    mut transition: ResMut<ScreenTransition>,
     mut levels: ResMut<Levels>,
+    mut campaign: ResMut<CampaignStats>,
+    buttons: Query<(&VictoryButton, &Interaction)>,
     keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
 ) {
-    if keyboard_input.pressed(KeyCode::X) {
-        levels.current_level = 0;
-        state.set(AppState::Loading);
+    let count = levels.levels.len() + 1;
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        menu.selected = (menu.selected + count - 1) % count;
     }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        menu.selected = (menu.selected + 1) % count;
+    }
+
+    let mut pressed = None;
+    for (button, interaction) in buttons.iter() {
+        match interaction {
+            Interaction::Clicked => pressed = Some(*button),
+            Interaction::Hovered => menu.selected = button.index(levels.levels.len()),
+            Interaction::None => {}
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        pressed = buttons
+            .iter()
+            .map(|(button, _)| *button)
+            .find(|button| button.index(levels.levels.len()) == menu.selected);
+    }
+    if keyboard_input.just_pressed(settings.controls.restart) {
+        pressed = Some(VictoryButton::PlayAgain);
+    }
+
+    match pressed {
+        Some(VictoryButton::ReplayLevel(level)) => {
+            levels.current_level = level;
+            levels.single_level = true;
+        }
+        Some(VictoryButton::PlayAgain) => {
+            levels.current_level = 0;
+            levels.single_level = false;
+            *campaign = CampaignStats::default();
+        }
+        None => return,
+    }
+    transition.cover();
 }
 
-fn despawn_win_screen(
-    mut commands: Commands,
-    text_query: Query<Entity, With<RootNode>>,
-    background_query: Query<Entity, With<VictoryScreen>>,
+fn highlight_selected_button(
+    menu: Res<VictoryMenu>,
+    levels: Res<Levels>,
+    mut buttons: Query<(&VictoryButton, &mut BackgroundColor)>,
 ) {
-    let entity = text_query.single();
-    commands.entity(entity).despawn_recursive();
+    for (button, mut background) in buttons.iter_mut() {
+        *background = if button.index(levels.levels.len()) == menu.selected {
+            SELECTED_COLOR.into()
+        } else {
+            Color::NONE.into()
+        };
+    }
+}
+
+fn load_selected_level(mut state: ResMut<NextState<AppState>>) {
+    state.set(AppState::Loading);
+}
 
-    let entity = background_query.single();
+fn despawn_victory_panel(mut commands: Commands, query: Query<Entity, With<RootNode>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn despawn_win_screen(mut commands: Commands, query: Query<Entity, With<VictoryScreen>>) {
+    let entity = query.single();
     commands.entity(entity).despawn();
 }
 
 pub struct WinScreenPlugin;
 impl Plugin for WinScreenPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(spawn_end_screen.in_schedule(OnEnter(AppState::Finished)))
-            .add_system(restart.in_set(OnUpdate(AppState::Finished)))
-            .add_system(despawn_win_screen.in_schedule(OnExit(AppState::Finished)));
+        app.init_resource::<VictoryMenu>()
+            .add_systems(
+                (spawn_end_screen, spawn_victory_panel).in_schedule(OnEnter(AppState::Finished)),
+            )
+            .add_systems(
+                (
+                    victory_menu_input
+                        .run_if(no_transition)
+                        .run_if(settings_closed),
+                    highlight_selected_button.after(victory_menu_input),
+                    load_selected_level.run_if(screen_covered),
+                )
+                    .in_set(OnUpdate(AppState::Finished)),
+            )
+            // switching language in the settings menu
+            .add_systems(
+                (despawn_victory_panel, spawn_victory_panel)
+                    .chain()
+                    .distributive_run_if(resource_changed::<Localization>())
+                    .in_set(OnUpdate(AppState::Finished)),
+            )
+            .add_systems(
+                (despawn_victory_panel, despawn_win_screen).in_schedule(OnExit(AppState::Finished)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_format() {
+        assert_eq!(format_time(7.25), "0:07.2");
+        assert_eq!(format_time(83.0), "1:23.0");
     }
 }